@@ -1,7 +1,17 @@
-use geo::{LineString, Polygon};
+use geo::{BooleanOps, Coord, LineString, MultiPolygon, Polygon};
 
 use crate::OffsetCurve;
 
+/// Make a polygon covering some width on each side of a linestring, using `OffsetCurve`. This is
+/// the most robust option, handling sharp angles with mitre limits and bridging pinched elbows.
+///
+/// `buffer_linestring_miter` is a simpler alternative that moves each vertex along its bisector.
+/// It always produces exactly one point per input vertex on each side, so output points line up
+/// with input points, which is handy for per-vertex styling. It doesn't bridge pinched elbows
+/// though, so on tight bends, the inner side may overlap itself.
+///
+/// Both variants glue the two sides together as-is, so where the sides cross each other at a tight
+/// bend, the polygon is invalid. Use `buffer_linestring_valid` when that matters.
 pub fn buffer_linestring(
     linestring: &LineString,
     left_meters: f64,
@@ -17,3 +27,182 @@ pub fn buffer_linestring(
     pts.extend(right.0);
     Some(Polygon::new(LineString(pts), Vec::new()))
 }
+
+/// Like `buffer_linestring`, but the result is always valid. Self-intersections are resolved by
+/// unioning the polygon with itself, which may split it into several pieces or leave holes. This
+/// is slower, so only use it when the output must be valid, like for exporting.
+pub fn buffer_linestring_valid(
+    linestring: &LineString,
+    left_meters: f64,
+    right_meters: f64,
+) -> Option<MultiPolygon> {
+    let polygon = MultiPolygon::new(vec![buffer_linestring(
+        linestring,
+        left_meters,
+        right_meters,
+    )?]);
+    Some(polygon.union(&MultiPolygon::new(Vec::new())))
+}
+
+/// Like `buffer_linestring`, but moving each vertex along the bisector of its two segments. This
+/// is `buffer_linestring_tapered` with constant widths. See `buffer_linestring` for the
+/// tradeoffs.
+pub fn buffer_linestring_miter(
+    linestring: &LineString,
+    left_meters: f64,
+    right_meters: f64,
+) -> Option<Polygon> {
+    buffer_linestring_tapered(
+        linestring,
+        left_meters,
+        left_meters,
+        right_meters,
+        right_meters,
+    )
+}
+
+/// Like `buffer_linestring`, but the width on each side changes linearly along the line, from the
+/// `start` value at the first point to the `end` value at the last. Each vertex is moved along the
+/// bisector of its two segments, with the same miter limit as `OffsetCurve`.
+pub fn buffer_linestring_tapered(
+    linestring: &LineString,
+    left_start: f64,
+    left_end: f64,
+    right_start: f64,
+    right_end: f64,
+) -> Option<Polygon> {
+    assert!(left_start >= 0.0 && left_end >= 0.0);
+    assert!(right_start >= 0.0 && right_end >= 0.0);
+
+    let mut pts = linestring.0.clone();
+    pts.dedup();
+    if pts.len() < 2 {
+        return None;
+    }
+    let length: f64 = pts
+        .windows(2)
+        .map(|pair| magnitude(pair[1] - pair[0]))
+        .sum();
+
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    let mut dist = 0.0;
+    for idx in 0..pts.len() {
+        if idx > 0 {
+            dist += magnitude(pts[idx] - pts[idx - 1]);
+        }
+        let fraction = dist / length;
+        let left_width = left_start + (left_end - left_start) * fraction;
+        let right_width = right_start + (right_end - right_start) * fraction;
+
+        let normal = vertex_normal(&pts, idx);
+        left.push(pts[idx] + normal * left_width);
+        right.push(pts[idx] - normal * right_width);
+    }
+
+    // Make a polygon by gluing these points together
+    left.reverse();
+    left.extend(right);
+    Some(Polygon::new(LineString(left), Vec::new()))
+}
+
+/// Points to the same side as `offset_curve` with a negative distance, scaled so that moving by
+/// it keeps a perpendicular distance of 1 from both adjacent segments
+fn vertex_normal(pts: &[Coord], idx: usize) -> Coord {
+    // Perpendicular to a segment, with unit length
+    let segment_normal = |a: Coord, b: Coord| {
+        let len = magnitude(b - a);
+        Coord {
+            x: (b.y - a.y) / len,
+            y: -(b.x - a.x) / len,
+        }
+    };
+
+    if idx == 0 {
+        return segment_normal(pts[0], pts[1]);
+    }
+    let n1 = segment_normal(pts[idx - 1], pts[idx]);
+    if idx == pts.len() - 1 {
+        return n1;
+    }
+    let n2 = segment_normal(pts[idx], pts[idx + 1]);
+
+    let bisector = n1 + n2;
+    let bisector_len = magnitude(bisector);
+    // The line doubles back on itself
+    if bisector_len < 1e-9 {
+        return n1;
+    }
+    let bisector = bisector / bisector_len;
+    let cos = bisector.x * n1.x + bisector.y * n1.y;
+    // Same miter limit factor as OffsetCurve
+    bisector * (1.0 / cos).min(2.0)
+}
+
+fn magnitude(pt: Coord) -> f64 {
+    pt.x.hypot(pt.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{line_string, Area, EuclideanLength};
+
+    use super::*;
+
+    #[test]
+    fn compare_on_zigzag() {
+        let input = line_string![
+            (x: 0.0, y: 0.0),
+            (x: 10.0, y: 10.0),
+            (x: 20.0, y: 0.0),
+            (x: 30.0, y: 10.0),
+        ];
+        let expected_area = input.euclidean_length() * 3.0;
+        let offset = buffer_linestring(&input, 1.0, 2.0).unwrap();
+        let miter = buffer_linestring_miter(&input, 1.0, 2.0).unwrap();
+
+        // The miter version has exactly one point per input vertex on each side, plus closing
+        assert_eq!(miter.exterior().0.len(), 2 * input.0.len() + 1);
+        // At these gentle angles, both cover about the same area
+        for polygon in [offset, miter] {
+            let area = polygon.unsigned_area();
+            assert!((area - expected_area).abs() / expected_area < 0.05);
+        }
+    }
+
+    #[test]
+    fn valid_on_hairpin() {
+        // Both sides are wider than the gap, so the naive buffer overlaps itself
+        let input = line_string![
+            (x: 0.0, y: 0.0),
+            (x: 10.0, y: 0.0),
+            (x: 10.0, y: 1.0),
+            (x: 0.0, y: 1.0),
+        ];
+        let valid = buffer_linestring_valid(&input, 2.0, 2.0).unwrap();
+        assert!(!valid.0.is_empty());
+        // The union can't cover more than the bounding box of the buffer
+        assert!(valid.unsigned_area() > 0.0 && valid.unsigned_area() <= 14.0 * 5.0);
+
+        let straight = line_string![(x: 0.0, y: 0.0), (x: 10.0, y: 0.0)];
+        let valid = buffer_linestring_valid(&straight, 1.0, 1.0).unwrap();
+        assert_eq!(valid.0.len(), 1);
+        assert!((valid.unsigned_area() - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tapered_straight_line() {
+        let input = line_string![(x: 0.0, y: 0.0), (x: 10.0, y: 0.0)];
+        let polygon = buffer_linestring_tapered(&input, 2.0, 4.0, 1.0, 1.0).unwrap();
+        assert_eq!(
+            polygon.exterior(),
+            &line_string![
+                (x: 10.0, y: -4.0),
+                (x: 0.0, y: -2.0),
+                (x: 0.0, y: 1.0),
+                (x: 10.0, y: 1.0),
+                (x: 10.0, y: -4.0),
+            ]
+        );
+    }
+}
@@ -1,3 +1,9 @@
+use std::collections::BinaryHeap;
+
+use anyhow::{bail, Result};
+
+use crate::PriorityQueueItem;
+
 /// A 2D grid containing some arbitrary data.
 pub struct Grid<T> {
     /// Logically represents a 2D vector. Row-major ordering.
@@ -15,6 +21,68 @@ impl<T: Copy> Grid<T> {
         }
     }
 
+    /// Build from rows of equal length
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Result<Grid<T>> {
+        let height = rows.len();
+        let width = rows.first().map(|row| row.len()).unwrap_or(0);
+        if let Some(row) = rows.iter().find(|row| row.len() != width) {
+            bail!("Rows have different lengths: {} and {}", width, row.len());
+        }
+        Ok(Grid {
+            data: rows.into_iter().flatten().collect(),
+            width,
+            height,
+        })
+    }
+
+    /// Transform every cell, keeping the same dimensions
+    pub fn map<U: Copy, F: Fn(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            data: self.data.iter().map(f).collect(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Render to a row-major RGBA buffer, with 4 bytes per cell, for debugging. Returns the
+    /// image's width and height in pixels along with the buffer, so it can be passed directly to
+    /// an image library or a canvas. Requires the `debug-image` feature.
+    #[cfg(feature = "debug-image")]
+    pub fn to_rgba<F: Fn(T) -> [u8; 4]>(&self, palette: F) -> (usize, usize, Vec<u8>) {
+        let mut buffer = Vec::with_capacity(self.data.len() * 4);
+        for value in &self.data {
+            buffer.extend(palette(*value));
+        }
+        (self.width, self.height, buffer)
+    }
+
+    /// Shrink the grid by grouping `factor` x `factor` blocks of cells, and reducing each block
+    /// with `combine`. If the dimensions aren't divisible by `factor`, the blocks on the right and
+    /// bottom edges are smaller. Cells in each block are passed in row-major order.
+    pub fn downsample<F: Fn(&[T]) -> T>(&self, factor: usize, combine: F) -> Grid<T> {
+        let factor = factor.max(1);
+        let width = self.width.div_ceil(factor);
+        let height = self.height.div_ceil(factor);
+        let mut data = Vec::with_capacity(width * height);
+        let mut block = Vec::with_capacity(factor * factor);
+        for y in 0..height {
+            for x in 0..width {
+                block.clear();
+                for y2 in (y * factor)..((y + 1) * factor).min(self.height) {
+                    for x2 in (x * factor)..((x + 1) * factor).min(self.width) {
+                        block.push(self.data[self.idx(x2, y2)]);
+                    }
+                }
+                data.push(combine(&block));
+            }
+        }
+        Grid {
+            data,
+            width,
+            height,
+        }
+    }
+
     /// Calculate the index from a given (x, y). Doesn't do any bounds checking.
     pub fn idx(&self, x: usize, y: usize) -> usize {
         y * self.width + x
@@ -27,12 +95,85 @@ impl<T: Copy> Grid<T> {
         (x, y)
     }
 
+    /// Returns `None` if (x, y) is out of bounds.
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.data.get(self.idx(x, y))
+    }
+
+    /// Returns `None` if (x, y) is out of bounds.
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let idx = self.idx(x, y);
+        self.data.get_mut(idx)
+    }
+
+    /// Returns false and does nothing if (x, y) is out of bounds.
+    pub fn set(&mut self, x: usize, y: usize, value: T) -> bool {
+        if let Some(cell) = self.get_mut(x, y) {
+            *cell = value;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Iterate over every cell, with its (x, y)
+    pub fn iter_with_coords(&self) -> impl Iterator<Item = (usize, usize, &T)> {
+        self.data.iter().enumerate().map(|(idx, value)| {
+            let (x, y) = self.xy(idx);
+            (x, y, value)
+        })
+    }
+
+    /// Iterate over each row, from top to bottom
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // chunks panics on 0
+        self.data.chunks(self.width.max(1))
+    }
+
     /// From one tile, calculate the 4 orthogonal neighbors. Includes bounds checking.
     pub fn orthogonal_neighbors(&self, center_x: usize, center_y: usize) -> Vec<(usize, usize)> {
+        self.neighbors(center_x, center_y, &[(-1, 0), (0, -1), (0, 1), (1, 0)])
+    }
+
+    /// From one tile, calculate the 4 diagonal neighbors. Includes bounds checking.
+    pub fn diagonal_neighbors(&self, center_x: usize, center_y: usize) -> Vec<(usize, usize)> {
+        self.neighbors(center_x, center_y, &[(-1, -1), (-1, 1), (1, -1), (1, 1)])
+    }
+
+    /// From one tile, calculate all 8 neighbors. Includes bounds checking.
+    pub fn all_neighbors(&self, center_x: usize, center_y: usize) -> Vec<(usize, usize)> {
+        self.neighbors(
+            center_x,
+            center_y,
+            &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+        )
+    }
+
+    fn neighbors(
+        &self,
+        center_x: usize,
+        center_y: usize,
+        offsets: &[(isize, isize)],
+    ) -> Vec<(usize, usize)> {
         let center_x = center_x as isize;
         let center_y = center_y as isize;
         let mut results = Vec::new();
-        for (dx, dy) in [(-1, 0), (0, -1), (0, 1), (1, 0)] {
+        for (dx, dy) in offsets {
             let x = center_x + dx;
             let y = center_y + dy;
             if x < 0 || (x as usize) >= self.width || y < 0 || (y as usize) >= self.height {
@@ -43,3 +184,149 @@ impl<T: Copy> Grid<T> {
         results
     }
 }
+
+impl Grid<f64> {
+    /// Calculate the minimum cost to reach every cell from any of the `sources`, moving between
+    /// orthogonal neighbors. `passable` returns the cost to enter a cell, or `None` if it's
+    /// blocked. Sources always cost 0, and unreachable cells are `f64::INFINITY`. Negative or NaN
+    /// costs are treated as blocked.
+    ///
+    /// `self` is only used for its dimensions.
+    pub fn flood_fill<F: Fn((usize, usize)) -> Option<f64>>(
+        &self,
+        sources: &[(usize, usize)],
+        passable: F,
+    ) -> Grid<f64> {
+        let mut costs = Grid::new(self.width, self.height, f64::INFINITY);
+        // Non-negative floats sort the same way as their bit representation
+        let mut queue = BinaryHeap::new();
+        for (x, y) in sources {
+            if costs.set(*x, *y, 0.0) {
+                queue.push(PriorityQueueItem::new(0.0_f64.to_bits(), costs.idx(*x, *y)));
+            }
+        }
+
+        while let Some(current) = queue.pop() {
+            let cost = f64::from_bits(current.cost);
+            if cost > costs.data[current.value] {
+                continue;
+            }
+            let (x, y) = costs.xy(current.value);
+            for (nx, ny) in costs.orthogonal_neighbors(x, y) {
+                let Some(step) = passable((nx, ny)) else {
+                    continue;
+                };
+                if step.is_nan() || step < 0.0 {
+                    continue;
+                }
+                let next_cost = cost + step;
+                let idx = costs.idx(nx, ny);
+                if next_cost < costs.data[idx] {
+                    costs.data[idx] = next_cost;
+                    queue.push(PriorityQueueItem::new(next_cost.to_bits(), idx));
+                }
+            }
+        }
+        costs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_checking() {
+        let mut grid = Grid::new(3, 2, 0);
+        assert!(grid.set(2, 1, 5));
+        assert_eq!(grid.get(2, 1), Some(&5));
+        assert_eq!(grid.get(3, 0), None);
+        assert_eq!(grid.get(0, 2), None);
+        assert!(!grid.set(3, 0, 1));
+        assert!(grid.get_mut(0, 2).is_none());
+    }
+
+    #[test]
+    fn rows() {
+        let grid = Grid::from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]]).unwrap();
+        assert_eq!((grid.width, grid.height), (3, 2));
+        assert_eq!(
+            grid.rows().collect::<Vec<_>>(),
+            vec![&[1, 2, 3], &[4, 5, 6]]
+        );
+        assert_eq!(grid.iter_with_coords().nth(4), Some((1, 1, &5)));
+
+        assert!(Grid::from_rows(vec![vec![1, 2], vec![3]]).is_err());
+    }
+
+    #[test]
+    fn map() {
+        let mut grid = Grid::new(4, 2, 0_u32);
+        grid.set(1, 1, 3);
+        let mask: Grid<bool> = grid.map(|x| *x > 0);
+        assert_eq!((mask.width, mask.height), (4, 2));
+        assert_eq!(mask.data.iter().filter(|x| **x).count(), 1);
+        assert_eq!(mask.get(1, 1), Some(&true));
+    }
+
+    #[test]
+    fn neighbors() {
+        let grid = Grid::new(3, 3, 0);
+        assert_eq!(grid.orthogonal_neighbors(0, 0), vec![(0, 1), (1, 0)]);
+        assert_eq!(grid.diagonal_neighbors(0, 0), vec![(1, 1)]);
+        assert_eq!(grid.all_neighbors(0, 0), vec![(0, 1), (1, 0), (1, 1)]);
+        assert_eq!(grid.all_neighbors(1, 1).len(), 8);
+    }
+
+    #[test]
+    fn downsample() {
+        let grid = Grid::from_rows(vec![
+            vec![1, 2, 3, 4, 5],
+            vec![6, 7, 8, 9, 10],
+            vec![11, 12, 13, 14, 15],
+        ])
+        .unwrap();
+        let sums = grid.downsample(2, |block| block.iter().sum());
+        assert_eq!((sums.width, sums.height), (3, 2));
+        assert_eq!(sums.data, vec![16, 24, 15, 23, 27, 15]);
+
+        let max = grid.downsample(5, |block| *block.iter().max().unwrap());
+        assert_eq!(max.data, vec![15]);
+    }
+
+    #[cfg(feature = "debug-image")]
+    #[test]
+    fn to_rgba() {
+        let grid = Grid::from_rows(vec![vec![0_u8, 255, 0], vec![128, 0, 0]]).unwrap();
+        let (width, height, buffer) = grid.to_rgba(|x| [x, x, x, 255]);
+        assert_eq!((width, height), (3, 2));
+        assert_eq!(buffer.len(), 24);
+        assert_eq!(&buffer[4..8], &[255, 255, 255, 255]);
+        assert_eq!(&buffer[12..16], &[128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn flood_fill() {
+        // A wall in the middle column, except for the bottom row
+        let walls = Grid::from_rows(vec![
+            vec![false, true, false],
+            vec![false, true, false],
+            vec![false, false, false],
+        ])
+        .unwrap();
+        let costs = Grid::new(3, 3, 0.0).flood_fill(&[(0, 0)], |(x, y)| {
+            if *walls.get(x, y).unwrap() {
+                None
+            } else {
+                Some(1.0)
+            }
+        });
+        assert_eq!(costs.get(0, 0), Some(&0.0));
+        assert_eq!(costs.get(2, 0), Some(&6.0));
+        assert_eq!(costs.get(1, 0), Some(&f64::INFINITY));
+
+        // Sources out of bounds are ignored
+        let costs = Grid::new(2, 1, 0.0).flood_fill(&[(5, 5)], |_| Some(1.0));
+        assert!(costs.data.iter().all(|x| *x == f64::INFINITY));
+    }
+}
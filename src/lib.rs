@@ -7,14 +7,77 @@ mod node_map;
 mod offset_curve;
 pub mod osm2graph;
 mod priority_queue;
+mod step_along_line;
 mod tags;
 
-pub use self::buffer_linestring::buffer_linestring;
+pub use self::buffer_linestring::{
+    buffer_linestring, buffer_linestring_miter, buffer_linestring_tapered, buffer_linestring_valid,
+};
 pub use self::grid::Grid;
-pub use self::line_split::{LineSplit, LineSplitResult, LineSplitTwiceResult};
+pub use self::line_split::{
+    split_ring_at_fractions, LineSplit, LineSplitResult, LineSplitTwiceResult,
+};
 pub use self::mercator::Mercator;
 #[cfg(feature = "serde")]
 pub use self::node_map::{deserialize_nodemap, NodeMap};
-pub use self::offset_curve::OffsetCurve;
-pub use self::priority_queue::PriorityQueueItem;
-pub use self::tags::Tags;
+pub use self::offset_curve::{OffsetCurve, OffsetCurveDiagnostics};
+pub use self::priority_queue::{dijkstra, PriorityQueueItem};
+pub use self::step_along_line::{
+    fractional_position, point_at_distance, sample_evenly, step_along_line, step_along_line_from,
+    step_along_line_with_headings,
+};
+pub use self::tags::{LaneCounts, TagDiff, Tags, ENGLISH_ABBREVIATIONS};
+
+use geo::{BoundingRect, Point, Rect};
+use rstar::{Envelope, AABB};
+
+/// Calculates the bounding box of some geometry
+pub fn aabb<G: BoundingRect<f64, Output = Option<Rect>>>(geom: &G) -> AABB<Point> {
+    rect_to_aabb(geom.bounding_rect().unwrap())
+}
+
+/// Calculates the combined bounding box of many geometries. Returns `None` if there are none.
+pub fn aabb_of<'a, G, I>(geoms: I) -> Option<AABB<Point>>
+where
+    G: BoundingRect<f64> + 'a,
+    G::Output: Into<Option<Rect>>,
+    I: IntoIterator<Item = &'a G>,
+{
+    geoms
+        .into_iter()
+        .filter_map(|g| g.bounding_rect().into())
+        .map(rect_to_aabb)
+        .reduce(|a, b| a.merged(&b))
+}
+
+/// Expand a bounding box by the same amount on all sides
+pub fn buffer_aabb(aabb: AABB<Point>, buffer_meters: f64) -> AABB<Point> {
+    buffer_aabb_xy(aabb, buffer_meters, buffer_meters)
+}
+
+/// Expand a bounding box by `dx` on the left and right, and `dy` on the top and bottom
+pub fn buffer_aabb_xy(aabb: AABB<Point>, dx: f64, dy: f64) -> AABB<Point> {
+    buffer_aabb_sides(aabb, dx, dx, dy, dy)
+}
+
+/// Expand a bounding box by a different amount on each side. In world-space, `top` is the side
+/// with the smaller y.
+pub fn buffer_aabb_sides(
+    aabb: AABB<Point>,
+    left: f64,
+    right: f64,
+    top: f64,
+    bottom: f64,
+) -> AABB<Point> {
+    AABB::from_corners(
+        Point::new(aabb.lower().x() - left, aabb.lower().y() - top),
+        Point::new(aabb.upper().x() + right, aabb.upper().y() + bottom),
+    )
+}
+
+fn rect_to_aabb(bbox: Rect) -> AABB<Point> {
+    AABB::from_corners(
+        Point::new(bbox.min().x, bbox.min().y),
+        Point::new(bbox.max().x, bbox.max().y),
+    )
+}
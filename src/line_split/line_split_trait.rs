@@ -1,3 +1,4 @@
+use geo::EuclideanLength;
 use geo_types::CoordFloat;
 
 use super::{LineSplitResult, LineSplitTwiceResult};
@@ -121,6 +122,58 @@ where
         }
     }
 
+    /// Split a [Line](geo::Line) or [LineString](geo::LineString) at some distance `dist` along
+    /// it, measured in the same units as the coordinates.
+    ///
+    /// Like [.line_split()](LineSplit::line_split), distances outside of the line will be clamped
+    /// to the start or end.
+    ///
+    /// Returns [None] when
+    /// - The provided `dist` is NAN
+    /// - The object being sliced has zero length, or includes NAN or infinite coordinates
+    fn split_at_distance(&self, dist: Scalar) -> Option<LineSplitResult<Self>>
+    where
+        Self: EuclideanLength<Scalar>,
+    {
+        let length = self.euclidean_length();
+        if dist.is_nan() || !length.is_finite() || length.is_zero() {
+            return None;
+        }
+        self.line_split(dist / length)
+    }
+
+    /// Split a [Line](geo::Line) or [LineString](geo::LineString) at many `fractions`, returning
+    /// `fractions.len() + 1` parts in order. An empty list of fractions returns a copy of the
+    /// whole line.
+    ///
+    /// Returns `None` when
+    /// - The `fractions` are not strictly increasing
+    /// - Any of the `fractions` are outside of the range (0.0, 1.0), including NAN
+    /// - The the object being sliced includes NAN or infinite coordinates
+    ///
+    /// This uses [.line_split_many()](LineSplit::line_split_many) internally, but validates the
+    /// input instead of clamping and sorting it.
+    fn split_at_fractions(&self, fractions: &[Scalar]) -> Option<Vec<Self>>
+    where
+        Self: Clone,
+    {
+        if fractions
+            .iter()
+            .any(|f| f.is_nan() || *f <= Scalar::zero() || *f >= Scalar::one())
+        {
+            return None;
+        }
+        if fractions.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return None;
+        }
+        if fractions.is_empty() {
+            return Some(vec![self.clone()]);
+        }
+        self.line_split_many(&fractions.to_vec())?
+            .into_iter()
+            .collect()
+    }
+
     /// Split a [Line](geo::Line) or [LineString](geo::LineString)
     /// at `fraction_start` and at `fraction_end`.
     /// 
@@ -374,4 +374,67 @@ mod test {
             ]
         );
     }
+
+    // =============================================================================================
+    // LineString::split_at_fractions()
+    // =============================================================================================
+
+    #[test]
+    fn split_at_fractions() {
+        let line_string: LineString<f32> = line_string![
+            (x: 0.0, y: 0.0),
+            (x:10.0, y: 0.0),
+            (x:10.0, y:10.0),
+        ];
+        assert_eq!(
+            line_string.split_at_fractions(&[0.5, 0.75]),
+            Some(vec![
+                line_string![(x: 0.0, y: 0.0), (x:10.0, y: 0.0)],
+                line_string![(x:10.0, y: 0.0), (x:10.0, y: 5.0)],
+                line_string![(x:10.0, y: 5.0), (x:10.0, y:10.0)],
+            ])
+        );
+        assert_eq!(
+            line_string.split_at_fractions(&[]),
+            Some(vec![line_string.clone()])
+        );
+    }
+
+    #[test]
+    fn split_at_fractions_invalid() {
+        let line_string: LineString<f32> = line_string![
+            (x: 0.0, y: 0.0),
+            (x:10.0, y: 0.0),
+        ];
+        assert_eq!(line_string.split_at_fractions(&[0.5, 0.25]), None);
+        assert_eq!(line_string.split_at_fractions(&[0.5, 0.5]), None);
+        assert_eq!(line_string.split_at_fractions(&[0.0, 0.5]), None);
+        assert_eq!(line_string.split_at_fractions(&[0.5, 1.5]), None);
+        assert_eq!(line_string.split_at_fractions(&[f32::NAN]), None);
+    }
+
+    // =============================================================================================
+    // LineString::split_at_distance()
+    // =============================================================================================
+
+    #[test]
+    fn split_at_distance() {
+        let line_string: LineString<f32> = line_string![
+            (x: 0.0, y: 0.0),
+            (x:10.0, y: 0.0),
+            (x:10.0, y:10.0),
+        ];
+        assert_eq!(
+            line_string.split_at_distance(15.0),
+            Some(LineSplitResult::FirstSecond(
+                line_string![(x: 0.0, y: 0.0), (x:10.0, y: 0.0), (x:10.0, y: 5.0)],
+                line_string![(x:10.0, y: 5.0), (x:10.0, y:10.0)],
+            ))
+        );
+        assert_eq!(
+            line_string.split_at_distance(25.0),
+            Some(LineSplitResult::First(line_string.clone()))
+        );
+        assert_eq!(line_string.split_at_distance(f32::NAN), None);
+    }
 }
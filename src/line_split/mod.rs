@@ -16,6 +16,10 @@ pub use line_split_result::LineSplitResult;
 mod line_split_twice_result;
 pub use line_split_twice_result::LineSplitTwiceResult;
 
+/// Split a closed ring into two arcs
+mod split_ring;
+pub use split_ring::split_ring_at_fractions;
+
 /// Helper function to measure the total length
 /// of a LineString at the same time as the length of each segment
 mod measure_line_string;
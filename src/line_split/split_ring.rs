@@ -0,0 +1,115 @@
+use geo::{Coord, LineString};
+
+use super::LineSplit;
+
+/// Split a closed ring, like a [Polygon](geo::Polygon)'s exterior, at two fractions of its
+/// length. Returns two arcs: the first goes from `f1` to `f2` following the ring's order, wrapping
+/// around the ring's start if `f1 > f2`. The second continues from `f2` back to `f1`.
+///
+/// Returns [None] when
+/// - The ring isn't closed, or has fewer than 4 points
+/// - Either fraction is NAN or outside of [0.0, 1.0]
+/// - The fractions are the same position on the ring (including 0.0 and 1.0)
+/// - The ring has zero length, or includes NAN or infinite coordinates
+pub fn split_ring_at_fractions(
+    ring: &LineString,
+    f1: f64,
+    f2: f64,
+) -> Option<(LineString, LineString)> {
+    if ring.0.len() < 4 || !ring.is_closed() {
+        return None;
+    }
+    if ![f1, f2].iter().all(|f| (0.0..=1.0).contains(f)) {
+        return None;
+    }
+    let (start, end) = if f1 < f2 { (f1, f2) } else { (f2, f1) };
+    if start == end || (start == 0.0 && end == 1.0) {
+        return None;
+    }
+
+    let (before, middle, after) = ring.line_split_twice(start, end)?.into_tuple();
+    let middle = middle?;
+    // The part after `end` continues through the ring's start to the part before `start`. Both
+    // pieces share the ring's start point, so only include it once.
+    let mut wrapped: Vec<Coord> = after.map(|ls| ls.0).unwrap_or_default();
+    if let Some(before) = before {
+        let skip = usize::from(!wrapped.is_empty());
+        wrapped.extend(before.0.into_iter().skip(skip));
+    }
+    let wrapped = LineString::new(wrapped);
+
+    if f1 < f2 {
+        Some((middle, wrapped))
+    } else {
+        Some((wrapped, middle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::line_string;
+
+    use super::*;
+
+    fn square() -> LineString {
+        line_string![
+            (x: 0.0, y: 0.0),
+            (x: 10.0, y: 0.0),
+            (x: 10.0, y: 10.0),
+            (x: 0.0, y: 10.0),
+            (x: 0.0, y: 0.0),
+        ]
+    }
+
+    fn assert_close(actual: &LineString, expected: LineString) {
+        assert_eq!(
+            actual.0.len(),
+            expected.0.len(),
+            "{actual:?} != {expected:?}"
+        );
+        for (a, b) in actual.coords().zip(expected.coords()) {
+            assert!((a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn split_square() {
+        let (arc1, arc2) = split_ring_at_fractions(&square(), 0.125, 0.625).unwrap();
+        assert_close(
+            &arc1,
+            line_string![(x: 5.0, y: 0.0), (x: 10.0, y: 0.0), (x: 10.0, y: 10.0), (x: 5.0, y: 10.0)],
+        );
+        assert_close(
+            &arc2,
+            line_string![(x: 5.0, y: 10.0), (x: 0.0, y: 10.0), (x: 0.0, y: 0.0), (x: 5.0, y: 0.0)],
+        );
+
+        // Reversing the fractions wraps around the start instead
+        let (reversed1, reversed2) = split_ring_at_fractions(&square(), 0.625, 0.125).unwrap();
+        assert_close(&reversed1, arc2);
+        assert_close(&reversed2, arc1);
+    }
+
+    #[test]
+    fn split_at_ring_start() {
+        let (arc1, arc2) = split_ring_at_fractions(&square(), 0.0, 0.5).unwrap();
+        assert_close(
+            &arc1,
+            line_string![(x: 0.0, y: 0.0), (x: 10.0, y: 0.0), (x: 10.0, y: 10.0)],
+        );
+        assert_close(
+            &arc2,
+            line_string![(x: 10.0, y: 10.0), (x: 0.0, y: 10.0), (x: 0.0, y: 0.0)],
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(split_ring_at_fractions(&square(), 0.3, 0.3).is_none());
+        assert!(split_ring_at_fractions(&square(), 0.0, 1.0).is_none());
+        assert!(split_ring_at_fractions(&square(), f64::NAN, 0.5).is_none());
+        assert!(split_ring_at_fractions(&square(), 0.5, 1.5).is_none());
+        let open = line_string![(x: 0.0, y: 0.0), (x: 10.0, y: 0.0), (x: 10.0, y: 10.0), (x: 0.0, y: 10.0)];
+        assert!(split_ring_at_fractions(&open, 0.2, 0.5).is_none());
+    }
+}
@@ -1,4 +1,6 @@
-use geo::{BoundingRect, Coord, HaversineLength, LineString, MapCoords, MapCoordsInPlace, Rect};
+use geo::{
+    BoundingRect, Coord, HaversineLength, LineString, MapCoords, MapCoordsInPlace, Point, Rect,
+};
 use geojson::{Feature, Geometry, Value};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -8,6 +10,7 @@ use serde::{Deserialize, Serialize};
 /// The accuracy of this weakens for larger areas.
 // TODO Upstream or consider https://github.com/georust/geo/issues/1165
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, PartialEq)]
 pub struct Mercator {
     pub wgs84_bounds: Rect,
     pub width: f64,
@@ -19,6 +22,46 @@ impl Mercator {
     /// Create a boundary covering some geometry
     pub fn from<T: BoundingRect<f64>>(geometry: T) -> Option<Self> {
         let wgs84_bounds = geometry.bounding_rect().into()?;
+        Some(Self::new_from_bounds(wgs84_bounds))
+    }
+
+    /// Create a boundary covering some geometry, expanded by a margin on all sides, so that
+    /// geometry added just outside won't have negative coordinates. This works for a single
+    /// point or a straight line along one axis, as long as the margin is positive. Returns `None`
+    /// if the result would still have zero width or height.
+    pub fn from_with_margin<T: BoundingRect<f64>>(geometry: T, margin_meters: f64) -> Option<Self> {
+        let bounds: Rect = geometry.bounding_rect().into()?;
+        // Measure around the center, since meters_per_degree is undefined for empty bounds
+        let center = bounds.center();
+        let step = 0.001;
+        let meters_per_lon =
+            LineString::from(vec![(center.x, center.y), (center.x + step, center.y)])
+                .haversine_length()
+                / step;
+        let meters_per_lat =
+            LineString::from(vec![(center.x, center.y), (center.x, center.y + step)])
+                .haversine_length()
+                / step;
+        let dx = margin_meters / meters_per_lon;
+        let dy = margin_meters / meters_per_lat;
+        if bounds.width() + 2.0 * dx <= 0.0 || bounds.height() + 2.0 * dy <= 0.0 {
+            return None;
+        }
+        Some(Self::new_from_bounds(Rect::new(
+            Coord {
+                x: bounds.min().x - dx,
+                y: bounds.min().y - dy,
+            },
+            Coord {
+                x: bounds.max().x + dx,
+                y: bounds.max().y + dy,
+            },
+        )))
+    }
+
+    /// Create from a WGS84 bounding box, like a saved `wgs84_bounds`. The same bounds always
+    /// produce the same projection.
+    pub fn new_from_bounds(wgs84_bounds: Rect) -> Self {
         let width = LineString::from(vec![
             (wgs84_bounds.min().x, wgs84_bounds.min().y),
             (wgs84_bounds.max().x, wgs84_bounds.min().y),
@@ -29,13 +72,94 @@ impl Mercator {
             (wgs84_bounds.min().x, wgs84_bounds.max().y),
         ])
         .haversine_length();
-        Some(Self {
+        Self {
             wgs84_bounds,
             width,
             height,
-        })
+        }
+    }
+
+    /// Create from explicit WGS84 bounds, like ones stored in a config file. Equivalent to
+    /// `new_from_bounds`.
+    pub fn from_bbox(min_lon: f64, min_lat: f64, max_lon: f64, max_lat: f64) -> Self {
+        Self::new_from_bounds(Rect::new(
+            Coord {
+                x: min_lon,
+                y: min_lat,
+            },
+            Coord {
+                x: max_lon,
+                y: max_lat,
+            },
+        ))
+    }
+
+    /// True if both projections use the same WGS84 bounds, within `epsilon` degrees on every
+    /// side. The world-space size is derived from the bounds, so it'll also match. Use this to
+    /// check that geometry from two sources can be combined.
+    pub fn approx_eq(&self, other: &Mercator, epsilon: f64) -> bool {
+        let close =
+            |a: Coord, b: Coord| (a.x - b.x).abs() <= epsilon && (a.y - b.y).abs() <= epsilon;
+        close(self.wgs84_bounds.min(), other.wgs84_bounds.min())
+            && close(self.wgs84_bounds.max(), other.wgs84_bounds.max())
+    }
+
+    /// The width of the projected area in meters
+    pub fn width_meters(&self) -> f64 {
+        self.width
+    }
+
+    /// The height of the projected area in meters
+    pub fn height_meters(&self) -> f64 {
+        self.height
+    }
+
+    /// The WGS84 bounding box covered by this projection
+    pub fn wgs84_bounds(&self) -> Rect {
+        self.wgs84_bounds
+    }
+
+    /// The scale factors used to project, in meters per degree of longitude and latitude
+    pub fn meters_per_degree(&self) -> (f64, f64) {
+        (
+            self.width / self.wgs84_bounds.width(),
+            self.height / self.wgs84_bounds.height(),
+        )
+    }
+
+    /// Scale a point in world-space into [0, 1] on both axes. Like world-space, the top-left is
+    /// (0, 0), suitable for screen coordinates.
+    pub fn to_unit_square(&self, pt: Point) -> Point {
+        Point::new(pt.x() / self.width, pt.y() / self.height)
     }
 
+    /// Map a point in world-space to a pixel rectangle, like an offscreen canvas. World-space is
+    /// already in screen-drawing order, so y increases downwards and no flip is needed. The aspect
+    /// ratio is preserved by scaling uniformly to fit, then centering, so there's an empty margin
+    /// (letterboxing) along one axis when the shapes differ.
+    pub fn to_pixels(&self, pt: Point, width_px: u32, height_px: u32) -> (f64, f64) {
+        let (scale, offset_x, offset_y) = self.pixel_transform(width_px, height_px);
+        (pt.x() * scale + offset_x, pt.y() * scale + offset_y)
+    }
+
+    /// The inverse of `to_pixels`, for handling clicks
+    pub fn from_pixels(&self, x: f64, y: f64, width_px: u32, height_px: u32) -> Point {
+        let (scale, offset_x, offset_y) = self.pixel_transform(width_px, height_px);
+        Point::new((x - offset_x) / scale, (y - offset_y) / scale)
+    }
+
+    /// Returns (scale, offset_x, offset_y) to fit world-space into a pixel rectangle
+    fn pixel_transform(&self, width_px: u32, height_px: u32) -> (f64, f64, f64) {
+        let width_px = f64::from(width_px);
+        let height_px = f64::from(height_px);
+        let scale = (width_px / self.width).min(height_px / self.height);
+        let offset_x = (width_px - self.width * scale) / 2.0;
+        let offset_y = (height_px - self.height * scale) / 2.0;
+        (scale, offset_x, offset_y)
+    }
+
+    /// Project one WGS84 coordinate to world-space. This is the primitive; every other
+    /// `to_mercator` method goes through it.
     pub fn pt_to_mercator(&self, pt: Coord) -> Coord {
         let x = self.width * (pt.x - self.wgs84_bounds.min().x) / self.wgs84_bounds.width();
         // Invert y, so that the northernmost latitude is 0
@@ -55,10 +179,54 @@ impl Mercator {
         Coord { x, y }
     }
 
+    /// Transform a point in world-space to EPSG:3857 (web Mercator) meters, for aligning with
+    /// standard basemap tiles. This goes through WGS84, which is rounded to about 1cm. Note the
+    /// two projections disagree on distances; web Mercator is stretched away from the equator.
+    pub fn to_web_mercator(&self, pt: Point) -> Point {
+        let wgs84 = self.pt_to_wgs84(pt.into());
+        let x = EARTH_RADIUS_METERS * wgs84.x.to_radians();
+        let y = EARTH_RADIUS_METERS
+            * (std::f64::consts::FRAC_PI_4 + wgs84.y.to_radians() / 2.0)
+                .tan()
+                .ln();
+        Point::new(x, y)
+    }
+
+    /// The inverse of `to_web_mercator`
+    pub fn from_web_mercator(&self, pt: Point) -> Point {
+        let lon = (pt.x() / EARTH_RADIUS_METERS).to_degrees();
+        let lat = (2.0 * (pt.y() / EARTH_RADIUS_METERS).exp().atan() - std::f64::consts::FRAC_PI_2)
+            .to_degrees();
+        self.pt_to_mercator(Coord { x: lon, y: lat }).into()
+    }
+
+    /// Project any geometry (like `Point`, `LineString`, or `Polygon`) from WGS84 to world-space,
+    /// returning a copy
     pub fn to_mercator<G: MapCoords<f64, f64, Output = G>>(&self, geom: &G) -> G {
         geom.map_coords(|pt| self.pt_to_mercator(pt))
     }
 
+    /// Project a WGS84 rectangle to world-space. Since y is inverted, the corners swap roles;
+    /// the result is normalized so `min` is still less than `max` on both axes.
+    pub fn to_mercator_rect(&self, r: Rect) -> Rect {
+        Rect::new(self.pt_to_mercator(r.min()), self.pt_to_mercator(r.max()))
+    }
+
+    /// The inverse of `to_mercator_rect`, also normalized
+    pub fn to_wgs84_rect(&self, r: Rect) -> Rect {
+        Rect::new(self.pt_to_wgs84(r.min()), self.pt_to_wgs84(r.max()))
+    }
+
+    /// Project a flat slice of WGS84 coordinates to world-space, in place. This calls
+    /// `pt_to_mercator` on each coordinate, so the results are identical; use it to project a
+    /// whole vertex buffer in one tight loop, without going through a geometry type.
+    pub fn to_mercator_coords_in_place(&self, coords: &mut [Coord]) {
+        for pt in coords {
+            *pt = self.pt_to_mercator(*pt);
+        }
+    }
+
+    /// Unproject any geometry from world-space to WGS84, returning a copy
     pub fn to_wgs84<G: MapCoords<f64, f64, Output = G>>(&self, geom: &G) -> G {
         geom.map_coords(|pt| self.pt_to_wgs84(pt))
     }
@@ -70,17 +238,205 @@ impl Mercator {
         Feature::from(Geometry::from(Value::from(&self.to_wgs84(geom))))
     }
 
+    /// Like `to_mercator`, but modifies the input
     pub fn to_mercator_in_place<G: MapCoordsInPlace<f64>>(&self, geom: &mut G) {
         geom.map_coords_in_place(|pt| self.pt_to_mercator(pt));
     }
 
+    /// Like `to_wgs84`, but modifies the input
     pub fn to_wgs84_in_place<G: MapCoordsInPlace<f64>>(&self, geom: &mut G) {
         geom.map_coords_in_place(|pt| self.pt_to_wgs84(pt));
     }
+
+    /// Project any geometry, including interior rings and nested collections. This is the same
+    /// as `to_mercator_in_place`, but avoids needing to match on the enum.
+    pub fn to_mercator_geometry_in_place(&self, geom: &mut geo::Geometry) {
+        self.to_mercator_in_place(geom);
+    }
+
+    /// The inverse of `to_mercator_geometry_in_place`
+    pub fn to_wgs84_geometry_in_place(&self, geom: &mut geo::Geometry) {
+        self.to_wgs84_in_place(geom);
+    }
 }
 
+// The radius used by EPSG:3857
+const EARTH_RADIUS_METERS: f64 = 6_378_137.0;
+
 // Per https://datatracker.ietf.org/doc/html/rfc7946#section-11.2, 6 decimal places (10cm) is
 // plenty of precision
 fn trim_lon_lat(x: f64) -> f64 {
     (x * 10e6).round() / 10e6
 }
+
+#[cfg(test)]
+mod tests {
+    use geo::{polygon, MultiPolygon};
+
+    use super::*;
+
+    #[test]
+    fn geometry_round_trip() {
+        let outer = polygon!(
+            exterior: [
+                (x: -0.12, y: 51.50),
+                (x: -0.10, y: 51.50),
+                (x: -0.10, y: 51.52),
+                (x: -0.12, y: 51.52),
+            ],
+            interiors: [
+                [
+                    (x: -0.115, y: 51.505),
+                    (x: -0.105, y: 51.505),
+                    (x: -0.105, y: 51.515),
+                ],
+            ],
+        );
+        let other = polygon![
+            (x: -0.09, y: 51.53),
+            (x: -0.08, y: 51.53),
+            (x: -0.08, y: 51.54),
+        ];
+        let original = geo::Geometry::MultiPolygon(MultiPolygon(vec![outer, other]));
+        let mercator = Mercator::from(original.clone()).unwrap();
+
+        let mut geom = original.clone();
+        mercator.to_mercator_geometry_in_place(&mut geom);
+        assert_ne!(geom, original);
+        mercator.to_wgs84_geometry_in_place(&mut geom);
+
+        let geo::Geometry::MultiPolygon(actual) = geom else {
+            panic!("wrong geometry type");
+        };
+        let geo::Geometry::MultiPolygon(expected) = original else {
+            unreachable!()
+        };
+        let coords = |mp: &MultiPolygon| -> Vec<Coord> {
+            mp.iter()
+                .flat_map(|p| {
+                    std::iter::once(p.exterior())
+                        .chain(p.interiors())
+                        .flat_map(|ring| ring.0.clone())
+                })
+                .collect()
+        };
+        let actual = coords(&actual);
+        let expected = coords(&expected);
+        assert_eq!(actual.len(), expected.len());
+        for (pt1, pt2) in actual.into_iter().zip(expected) {
+            assert!((pt1.x - pt2.x).abs() < 1e-6 && (pt1.y - pt2.y).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn coords_in_place() {
+        let mercator =
+            Mercator::from(LineString::from(vec![(-0.12, 51.50), (-0.10, 51.52)])).unwrap();
+        let input = vec![
+            Coord { x: -0.12, y: 51.50 },
+            Coord { x: -0.11, y: 51.51 },
+            Coord { x: -0.10, y: 51.52 },
+        ];
+        let mut batch = input.clone();
+        mercator.to_mercator_coords_in_place(&mut batch);
+        for (pt, projected) in input.iter().zip(&batch) {
+            assert_eq!(mercator.pt_to_mercator(*pt), *projected);
+        }
+
+        // Geometry goes through the same formula
+        let mut linestring = LineString::new(input.clone());
+        mercator.to_mercator_in_place(&mut linestring);
+        assert_eq!(linestring.0, batch);
+
+        // Pin the order of operations, so outputs don't change in the last bits
+        let bounds = mercator.wgs84_bounds;
+        assert_eq!(
+            batch[1],
+            Coord {
+                x: mercator.width * (input[1].x - bounds.min().x) / bounds.width(),
+                y: mercator.height
+                    - mercator.height * (input[1].y - bounds.min().y) / bounds.height(),
+            }
+        );
+    }
+
+    #[test]
+    fn margin_around_point() {
+        let pt = Point::new(-0.1, 51.5);
+        let mercator = Mercator::from_with_margin(pt, 100.0).unwrap();
+        assert!((mercator.width - 200.0).abs() < 0.1);
+        assert!((mercator.height - 200.0).abs() < 0.1);
+        let projected = mercator.pt_to_mercator(pt.into());
+        assert!((projected.x - 100.0).abs() < 0.1 && (projected.y - 100.0).abs() < 0.1);
+
+        assert!(Mercator::from_with_margin(pt, 0.0).is_none());
+    }
+
+    #[test]
+    fn rects() {
+        let mercator = Mercator::from_bbox(-0.12, 51.50, -0.10, 51.52);
+        let wgs84 = Rect::new(
+            Coord {
+                x: -0.115,
+                y: 51.505,
+            },
+            Coord { x: -0.11, y: 51.51 },
+        );
+        let world = mercator.to_mercator_rect(wgs84);
+        assert!(world.min().x < world.max().x && world.min().y < world.max().y);
+        assert_eq!(world.min().y, mercator.pt_to_mercator(wgs84.max()).y);
+
+        let back = mercator.to_wgs84_rect(world);
+        assert!((back.min().x - wgs84.min().x).abs() < 1e-6);
+        assert!((back.max().y - wgs84.max().y).abs() < 1e-6);
+    }
+
+    #[test]
+    fn comparison() {
+        let mercator = Mercator::from_bbox(-0.12, 51.50, -0.10, 51.52);
+        assert_eq!(mercator, Mercator::from_bbox(-0.12, 51.50, -0.10, 51.52));
+
+        let shifted = Mercator::from_bbox(-0.12, 51.50, -0.10, 51.52000001);
+        assert_ne!(mercator, shifted);
+        assert!(mercator.approx_eq(&shifted, 1e-6));
+        assert!(!mercator.approx_eq(&shifted, 1e-9));
+    }
+
+    #[test]
+    fn pixels() {
+        let mercator = Mercator {
+            wgs84_bounds: Rect::new(Coord { x: 0.0, y: 0.0 }, Coord { x: 1.0, y: 1.0 }),
+            width: 200.0,
+            height: 100.0,
+        };
+        // The world is twice as wide as tall, so a square canvas has margins on the top and bottom
+        assert_eq!(
+            mercator.to_pixels(Point::new(0.0, 0.0), 400, 400),
+            (0.0, 100.0)
+        );
+        assert_eq!(
+            mercator.to_pixels(Point::new(200.0, 100.0), 400, 400),
+            (400.0, 300.0)
+        );
+        assert_eq!(
+            mercator.from_pixels(200.0, 200.0, 400, 400),
+            Point::new(100.0, 50.0)
+        );
+    }
+
+    #[test]
+    fn web_mercator() {
+        let mercator = Mercator::new_from_bounds(Rect::new(
+            Coord { x: -1.0, y: -1.0 },
+            Coord { x: 1.0, y: 1.0 },
+        ));
+        // The center of the bounds is (0, 0) in WGS84 and EPSG:3857
+        let center = Point::new(mercator.width / 2.0, mercator.height / 2.0);
+        let web = mercator.to_web_mercator(center);
+        assert!(web.x().abs() < 0.1 && web.y().abs() < 0.1);
+
+        let pt = Point::new(1234.5, 6789.0);
+        let round_trip = mercator.from_web_mercator(mercator.to_web_mercator(pt));
+        assert!((pt.x() - round_trip.x()).abs() < 0.1 && (pt.y() - round_trip.y()).abs() < 0.1);
+    }
+}
@@ -38,9 +38,16 @@ impl<T: Copy + Ord + Debug + Serialize> NodeMap<T> {
         self.node_to_id.get(&node).cloned()
     }
 
+    /// Translate a compact ID back to the original node. Panics if the ID wasn't produced by this
+    /// NodeMap.
     pub fn translate_id(&self, id: usize) -> T {
         self.id_to_node[id]
     }
+
+    /// Like `translate_id`, but returns `None` for an unknown ID
+    pub fn try_translate_id(&self, id: usize) -> Option<T> {
+        self.id_to_node.get(id).cloned()
+    }
 }
 
 // A serialized NodeMap has this form. Use this to deserialize.
@@ -5,4 +5,4 @@ mod offset_segments_iterator;
 mod vector_extensions;
 
 mod offset_curve_trait;
-pub use offset_curve_trait::OffsetCurve;
+pub use offset_curve_trait::{OffsetCurve, OffsetCurveDiagnostics};
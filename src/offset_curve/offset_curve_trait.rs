@@ -1,7 +1,8 @@
 // TODO: Should I be doing `use crate ::{...}` or `use geo_types::{...}`
-use geo::{Coord, CoordFloat, Line, LineString, MultiLineString};
+use geo::{Coord, CoordFloat, Line, LineString, MultiLineString, Polygon};
 
 use super::line_intersection::{
+    line_segment_intersection_with_relationships,
     FalseIntersectionPointType::AfterEnd,
     LineIntersectionResultWithRelationships,
     LineSegmentIntersectionType::{FalseIntersectionPoint, TrueIntersectionPoint},
@@ -18,7 +19,8 @@ use super::offset_segments_iterator::{LineStringOffsetSegmentPairs, OffsetSegmen
 /// The OffsetCurve trait is implemented for geometries where the edges of the
 /// geometry can be offset perpendicular to the direction of the edges by some
 /// positive or negative distance. For example, an offset [Line] will become a
-/// [Line], and an offset [LineString] will become a [LineString].
+/// [Line], and an offset [LineString] will become a [LineString]. The rings of
+/// a [Polygon] are offset as closed loops, insetting or outsetting the shape.
 /// Geometry with no length ([geo_types::Point]) cannot be offset as it has no
 /// directionality.
 ///
@@ -62,6 +64,32 @@ where
     /// assert_eq!(output_actual, output_expected);
     /// ```
     fn offset_curve(&self, distance: T) -> Option<Self>;
+
+    /// Like [OffsetCurve::offset_curve()], but where the output crosses itself, remove the loop
+    /// between the crossings. This happens when offsetting towards the inside of a tight hairpin
+    /// bend, producing a "bowtie" shape.
+    ///
+    /// The default implementation doesn't clean anything and just calls
+    /// [OffsetCurve::offset_curve()], so geometries that can produce bowties should override it.
+    /// [LineString] and [MultiLineString] do; [Line] can't cross itself, and [Polygon] rings are
+    /// always cleaned.
+    fn offset_curve_cleaned(&self, distance: T) -> Option<Self> {
+        self.offset_curve(distance)
+    }
+}
+
+/// Offsetting that also reports how well it worked. This is separate from [OffsetCurve], so
+/// geometries can implement that without this.
+pub trait OffsetCurveDiagnostics<T>: OffsetCurve<T>
+where
+    T: CoordFloat,
+{
+    /// Like [OffsetCurve::offset_curve()], but also returns the offset actually achieved at each
+    /// output vertex: the distance to the nearest point on the input, with the same sign as
+    /// `distance`. Where the output is pinched at a sharp concave corner, this is smaller in
+    /// magnitude than `distance`, so callers can detect where the offset is unreliable. Outside
+    /// corners may be slightly further away.
+    fn offset_curve_with_diagnostics(&self, distance: T) -> Option<(Self, Vec<T>)>;
 }
 
 impl<T> OffsetCurve<T> for Line<T>
@@ -86,6 +114,16 @@ where
     }
 }
 
+impl<T> OffsetCurveDiagnostics<T> for Line<T>
+where
+    T: CoordFloat,
+{
+    fn offset_curve_with_diagnostics(&self, distance: T) -> Option<(Self, Vec<T>)> {
+        // Both ends are always exactly offset
+        Some((self.offset_curve(distance)?, vec![distance; 2]))
+    }
+}
+
 impl<T> OffsetCurve<T> for LineString<T>
 where
     T: CoordFloat,
@@ -221,6 +259,97 @@ where
 
         Some(offset_points.into())
     }
+
+    fn offset_curve_cleaned(&self, distance: T) -> Option<Self> {
+        let offset = self.offset_curve(distance)?;
+        Some(remove_self_intersections(offset.0).into())
+    }
+}
+
+impl<T> OffsetCurveDiagnostics<T> for LineString<T>
+where
+    T: CoordFloat,
+{
+    fn offset_curve_with_diagnostics(&self, distance: T) -> Option<(Self, Vec<T>)> {
+        let offset = self.offset_curve(distance)?;
+        let achieved = achieved_offsets(self, &offset, distance);
+        Some((offset, achieved))
+    }
+}
+
+/// For every point in `output`, the distance to the nearest point on `input`, with the same sign
+/// as `distance`
+fn achieved_offsets<T>(input: &LineString<T>, output: &LineString<T>, distance: T) -> Vec<T>
+where
+    T: CoordFloat,
+{
+    let sign = if distance < T::zero() {
+        -T::one()
+    } else {
+        T::one()
+    };
+    output
+        .0
+        .iter()
+        .map(|pt| {
+            let nearest = input
+                .lines()
+                .map(|line| distance_to_segment(*pt, line))
+                .fold(T::infinity(), |a, b| a.min(b));
+            sign * nearest
+        })
+        .collect()
+}
+
+/// The distance from a point to the closest point on a segment
+fn distance_to_segment<T>(pt: Coord<T>, line: Line<T>) -> T
+where
+    T: CoordFloat,
+{
+    let ab = line.end - line.start;
+    let length_squared = ab.magnitude_squared();
+    if length_squared == T::zero() {
+        return (pt - line.start).magnitude();
+    }
+    let t = ((pt - line.start).dot_product(ab) / length_squared)
+        .max(T::zero())
+        .min(T::one());
+    (pt - (line.start + ab * t)).magnitude()
+}
+
+/// Repeatedly find two segments that cross, and cut out all the points between them, keeping the
+/// crossing point.
+fn remove_self_intersections<T>(mut pts: Vec<Coord<T>>) -> Vec<Coord<T>>
+where
+    T: CoordFloat,
+{
+    'restart: loop {
+        let num_segments = pts.len().saturating_sub(1);
+        for i in 0..num_segments {
+            // Adjacent segments always touch, so skip them
+            for j in (i + 2)..num_segments {
+                // The first and last segments of a closed ring touch
+                if i == 0 && j == num_segments - 1 && pts[0] == pts[pts.len() - 1] {
+                    continue;
+                }
+                if let Some(LineIntersectionResultWithRelationships {
+                    ab: TrueIntersectionPoint,
+                    cd: TrueIntersectionPoint,
+                    intersection,
+                }) = line_segment_intersection_with_relationships(
+                    pts[i],
+                    pts[i + 1],
+                    pts[j],
+                    pts[j + 1],
+                ) {
+                    // This always removes at least one point, so the loop terminates
+                    pts.splice(i + 1..=j, std::iter::once(intersection));
+                    continue 'restart;
+                }
+            }
+        }
+        return pts;
+    }
 }
 
 impl<T> OffsetCurve<T> for MultiLineString<T>
@@ -232,20 +361,168 @@ where
             .map(|item| item.offset_curve(distance))
             .collect()
     }
+
+    fn offset_curve_cleaned(&self, distance: T) -> Option<Self> {
+        self.iter()
+            .map(|item| item.offset_curve_cleaned(distance))
+            .collect()
+    }
+}
+
+impl<T> OffsetCurveDiagnostics<T> for MultiLineString<T>
+where
+    T: CoordFloat,
+{
+    /// The achieved offsets of every line are concatenated, in order
+    fn offset_curve_with_diagnostics(&self, distance: T) -> Option<(Self, Vec<T>)> {
+        let mut lines = Vec::new();
+        let mut achieved = Vec::new();
+        for item in self.iter() {
+            let (line, distances) = item.offset_curve_with_diagnostics(distance)?;
+            lines.push(line);
+            achieved.extend(distances);
+        }
+        Some((MultiLineString::new(lines), achieved))
+    }
+}
+
+impl<T> OffsetCurve<T> for Polygon<T>
+where
+    T: CoordFloat,
+{
+    /// Offset every ring of the polygon. The rings are first oriented in the standard way
+    /// (exterior counter-clockwise and interiors clockwise, in a coordinate system where positive
+    /// is up and to the right), so a positive `distance` insets the polygon and a negative one
+    /// outsets it.
+    ///
+    /// Returns [None] if the exterior can't be offset cleanly, like when insetting collapses the
+    /// shape. Interiors that collapse are removed.
+    fn offset_curve(&self, distance: T) -> Option<Self> {
+        Some(self.offset_curve_with_diagnostics(distance)?.0)
+    }
+}
+
+impl<T> OffsetCurveDiagnostics<T> for Polygon<T>
+where
+    T: CoordFloat,
+{
+    /// The achieved offsets of the exterior, then every remaining interior, are concatenated
+    fn offset_curve_with_diagnostics(&self, distance: T) -> Option<(Self, Vec<T>)> {
+        let exterior = oriented_ring(self.exterior(), true);
+        let (new_exterior, mut achieved) = offset_ring(&exterior, distance)?;
+        let mut interiors = Vec::new();
+        for ring in self.interiors() {
+            let ring = oriented_ring(ring, false);
+            if let Some((new_ring, ring_achieved)) = offset_ring(&ring, distance) {
+                interiors.push(new_ring);
+                achieved.extend(ring_achieved);
+            }
+        }
+        Some((Polygon::new(new_exterior, interiors), achieved))
+    }
+}
+
+/// Offset a closed ring, handling the corner where it wraps around. Returns [None] if the ring
+/// isn't closed, or if the result strays closer to the input than `distance`, meaning it
+/// collapsed.
+fn offset_ring<T>(ring: &LineString<T>, distance: T) -> Option<(LineString<T>, Vec<T>)>
+where
+    T: CoordFloat,
+{
+    if ring.0.len() < 4 || !ring.is_closed() {
+        return None;
+    }
+    if distance == T::zero() {
+        return Some((ring.clone(), vec![T::zero(); ring.0.len()]));
+    }
+    // Cutting out a crossing keeps the points outside the span between the two segments, so a
+    // lobe that collapses mustn't wrap around the start
+    let ring = &start_at_farthest_vertex(ring);
+
+    // Continue past the end onto the first segment again, so the corner at the start is treated
+    // like any other. The first and last output points are then on the same offset segment, so
+    // drop them and close the ring.
+    let mut extended = ring.0.clone();
+    extended.push(ring.0[1]);
+    let mut pts = LineString::new(extended).offset_curve(distance)?.0;
+    if pts.len() < 4 {
+        return None;
+    }
+    pts.pop();
+    pts.remove(0);
+    pts.push(pts[0]);
+    let output = LineString::new(remove_self_intersections(pts));
+    if output.0.len() < 4 {
+        return None;
+    }
+
+    let achieved = achieved_offsets(ring, &output, distance);
+    let tolerance = distance.abs() * T::from(1e-6).unwrap();
+    if achieved
+        .iter()
+        .any(|d| d.abs() < distance.abs() - tolerance)
+    {
+        return None;
+    }
+    Some((output, achieved))
+}
+
+/// Rotate a closed ring to start at the vertex farthest from the average of its vertices. That
+/// vertex is on the convex hull, so it's never inside a notch or spike that collapses when
+/// offsetting.
+fn start_at_farthest_vertex<T>(ring: &LineString<T>) -> LineString<T>
+where
+    T: CoordFloat,
+{
+    // Skip the repeated closing point
+    let pts = &ring.0[..ring.0.len() - 1];
+    let n = T::from(pts.len()).unwrap();
+    let origin = Coord {
+        x: T::zero(),
+        y: T::zero(),
+    };
+    let center = pts.iter().fold(origin, |sum, pt| sum + *pt) / n;
+    let mut start = 0;
+    for (idx, pt) in pts.iter().enumerate() {
+        if (*pt - center).magnitude_squared() > (pts[start] - center).magnitude_squared() {
+            start = idx;
+        }
+    }
+
+    let mut rotated = pts[start..].to_vec();
+    rotated.extend_from_slice(&pts[..start]);
+    rotated.push(pts[start]);
+    LineString::new(rotated)
+}
+
+/// Reverse a ring if needed, so that it's counter-clockwise or not
+fn oriented_ring<T>(ring: &LineString<T>, counter_clockwise: bool) -> LineString<T>
+where
+    T: CoordFloat,
+{
+    // Twice the signed area, positive when counter-clockwise
+    let area = ring
+        .lines()
+        .fold(T::zero(), |sum, line| sum + line.determinant());
+    if (area > T::zero()) == counter_clockwise {
+        ring.clone()
+    } else {
+        let mut reversed = ring.clone();
+        reversed.0.reverse();
+        reversed
+    }
 }
 
 #[cfg(test)]
 mod test {
 
     use geo::{
-        line_string,
-        Coord,
-        Line,
-        //LineString,
-        MultiLineString,
-        OffsetCurve,
+        line_intersection::{line_intersection, LineIntersection},
+        line_string, polygon, Area, Coord, Line, LineString, MultiLineString,
     };
 
+    use super::{OffsetCurve, OffsetCurveDiagnostics};
+
     #[test]
     fn test_offset_line() {
         let input = Line::new(Coord { x: 1f64, y: 1f64 }, Coord { x: 1f64, y: 2f64 });
@@ -291,6 +568,99 @@ mod test {
         assert_eq!(output_actual, output_expected);
     }
 
+    #[test]
+    fn test_offset_line_string_cleaned_hairpin() {
+        // A narrow U-turn. Offsetting to the inside by more than half its width crosses over.
+        let input = line_string![
+            Coord { x: 0f64, y: 0f64 },
+            Coord { x: 10f64, y: 0f64 },
+            Coord { x: 10f64, y: 1f64 },
+            Coord { x: 0f64, y: 1f64 },
+        ];
+        let has_crossing = |ls: &LineString| {
+            let lines: Vec<Line> = ls.lines().collect();
+            (0..lines.len()).any(|i| {
+                ((i + 2)..lines.len()).any(|j| match line_intersection(lines[i], lines[j]) {
+                    Some(LineIntersection::SinglePoint { .. }) => true,
+                    Some(LineIntersection::Collinear { .. }) => true,
+                    None => false,
+                })
+            })
+        };
+
+        let raw = input.offset_curve(2f64).unwrap();
+        assert!(has_crossing(&raw));
+        let cleaned = input.offset_curve_cleaned(2f64).unwrap();
+        assert!(!has_crossing(&cleaned));
+        assert_eq!(cleaned.0.first(), raw.0.first());
+        assert_eq!(cleaned.0.last(), raw.0.last());
+    }
+
+    #[test]
+    fn test_offset_line_string_with_diagnostics() {
+        let input = line_string![
+            Coord { x: 0f64, y: 0f64 },
+            Coord { x: 0f64, y: 2f64 },
+            Coord { x: 2f64, y: 2f64 },
+        ];
+        let (output, achieved) = input.offset_curve_with_diagnostics(-1f64).unwrap();
+        assert_eq!(Some(output), input.offset_curve(-1f64));
+        assert_eq!(achieved, vec![-1f64, -1f64, -1f64]);
+
+        // Offsetting to the inside of a narrow U-turn can't stay 2 away from the input everywhere
+        let hairpin = line_string![
+            Coord { x: 0f64, y: 0f64 },
+            Coord { x: 10f64, y: 0f64 },
+            Coord { x: 10f64, y: 1f64 },
+            Coord { x: 0f64, y: 1f64 },
+        ];
+        let (output, achieved) = hairpin.offset_curve_with_diagnostics(2f64).unwrap();
+        assert_eq!(output.0.len(), achieved.len());
+        assert!(achieved.iter().any(|d| *d < 2f64 - 1e-9));
+    }
+
+    #[test]
+    fn test_offset_polygon() {
+        // Clockwise, so it gets reversed first
+        let square = polygon![
+            (x: 0f64, y: 0f64),
+            (x: 0f64, y: 10f64),
+            (x: 10f64, y: 10f64),
+            (x: 10f64, y: 0f64),
+        ];
+
+        let inset = square.offset_curve(1f64).unwrap();
+        assert!(inset.exterior().is_closed());
+        assert_eq!(inset.exterior().0.len(), 5);
+        assert!((inset.unsigned_area() - 64f64).abs() < 1e-9);
+
+        let outset = square.offset_curve(-1f64).unwrap();
+        assert!((outset.unsigned_area() - 144f64).abs() < 1e-9);
+
+        // Insetting by more than half the width collapses the shape
+        assert_eq!(square.offset_curve(6f64), None);
+    }
+
+    #[test]
+    fn test_offset_polygon_starting_in_collapsing_spike() {
+        // A 20x10 rectangle with a narrow spike on top. The ring starts at the spike's tip.
+        let shape = polygon![
+            (x: 10f64, y: 20f64),
+            (x: 9f64, y: 10f64),
+            (x: 0f64, y: 10f64),
+            (x: 0f64, y: 0f64),
+            (x: 20f64, y: 0f64),
+            (x: 20f64, y: 10f64),
+            (x: 11f64, y: 10f64),
+        ];
+
+        // The spike collapses, leaving the inset rectangle
+        let inset = shape.offset_curve(1.5f64).unwrap();
+        assert!(inset.exterior().is_closed());
+        assert!((inset.unsigned_area() - 17f64 * 7f64).abs() < 1e-6);
+        assert!(inset.exterior().0.iter().all(|pt| pt.y <= 8.5f64 + 1e-9));
+    }
+
     #[test]
     fn test_offset_multi_line_string() {
         let input = MultiLineString::new(vec![
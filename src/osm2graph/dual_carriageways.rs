@@ -0,0 +1,295 @@
+use std::collections::{BTreeMap, HashSet};
+
+use geo::{Closest, ClosestPoint, Coord, EuclideanLength, LineString, Point};
+
+use super::{Direction, EdgeID, Graph, IntersectionID};
+use crate::Tags;
+
+impl Graph {
+    /// Collapse divided roads, mapped as two oneway edges with the same `name` or `ref`, into a
+    /// single two-way edge along the centerline. The edges must point in roughly opposite
+    /// directions, and every point of one must be within `max_separation_meters` of the other.
+    /// Intersections at the ends of the removed edge are joined with the nearest ends of the
+    /// kept edge. The kept edge remembers the other way in `merged_osm_ways`. IDs are renumbered
+    /// afterwards, like `remove_edges`.
+    ///
+    /// The kept edge's tags are rewritten to describe both carriageways, so they agree with its
+    /// new direction: `oneway=no`, and if either side had `lanes`, the lanes of each side become
+    /// `lanes:forward` and `lanes:backward`, with `lanes` as the total. Other tags only describe
+    /// the kept carriageway.
+    pub fn merge_dual_carriageways(&mut self, max_separation_meters: f64) {
+        // Group candidates by name or ref. BTreeMap for determinism.
+        let mut groups: BTreeMap<String, Vec<EdgeID>> = BTreeMap::new();
+        for e in &self.edges {
+            if e.direction == Direction::Both || e.src == e.dst {
+                continue;
+            }
+            if let Some(key) = e.osm_tags.get("name").or_else(|| e.osm_tags.get("ref")) {
+                groups.entry(key.clone()).or_default().push(e.id);
+            }
+        }
+
+        let mut remove = HashSet::new();
+        for edges in groups.into_values() {
+            for (idx, e1) in edges.iter().enumerate() {
+                for e2 in &edges[idx + 1..] {
+                    if remove.contains(e1) || remove.contains(e2) {
+                        continue;
+                    }
+                    if self.is_dual_carriageway(*e1, *e2, max_separation_meters)
+                        && self.merge_pair(*e1, *e2)
+                    {
+                        remove.insert(*e2);
+                        // Only merge each edge once
+                        break;
+                    }
+                }
+            }
+        }
+        self.remove_edges(remove);
+    }
+
+    fn is_dual_carriageway(&self, e1: EdgeID, e2: EdgeID, max_separation_meters: f64) -> bool {
+        let edge1 = &self.edges[e1.0];
+        let edge2 = &self.edges[e2.0];
+
+        // Compare the direction of travel
+        let travel = |linestring: &LineString, direction: Direction| {
+            let v = *linestring.0.last().unwrap() - linestring.0[0];
+            if direction == Direction::Backward {
+                -v
+            } else {
+                v
+            }
+        };
+        let v1 = travel(&edge1.linestring, edge1.direction);
+        let v2 = travel(&edge2.linestring, edge2.direction);
+        let cos = (v1.x * v2.x + v1.y * v2.y) / (v1.x.hypot(v1.y) * v2.x.hypot(v2.y));
+        // Within about 30 degrees of opposite
+        if cos.is_nan() || cos > -0.85 {
+            return false;
+        }
+
+        let all_close = |from: &LineString, to: &LineString| {
+            from.0.iter().all(|pt| {
+                distance_to(to, *pt).is_some_and(|(dist, _)| dist <= max_separation_meters)
+            })
+        };
+        all_close(&edge1.linestring, &edge2.linestring)
+            && all_close(&edge2.linestring, &edge1.linestring)
+    }
+
+    /// Keep `keep`, moving it to the centerline, and rewire everything touching `remove` to it.
+    /// `remove` still has to be deleted afterwards. Returns false and does nothing if the
+    /// endpoints can't be matched up.
+    fn merge_pair(&mut self, keep: EdgeID, remove: EdgeID) -> bool {
+        let (keep_src, keep_dst) = (self.edges[keep.0].src, self.edges[keep.0].dst);
+        let (remove_src, remove_dst) = (self.edges[remove.0].src, self.edges[remove.0].dst);
+
+        // Match up the endpoints of the two edges
+        let dist = |i1: IntersectionID, i2: IntersectionID| {
+            let (p1, p2) = (
+                self.intersections[i1.0].point,
+                self.intersections[i2.0].point,
+            );
+            (p1.x() - p2.x()).hypot(p1.y() - p2.y())
+        };
+        let pairs = if dist(keep_src, remove_src) + dist(keep_dst, remove_dst)
+            <= dist(keep_src, remove_dst) + dist(keep_dst, remove_src)
+        {
+            [(keep_src, remove_src), (keep_dst, remove_dst)]
+        } else {
+            [(keep_src, remove_dst), (keep_dst, remove_src)]
+        };
+        // Don't collapse the kept edge itself
+        if pairs
+            .iter()
+            .any(|(to, from)| to != from && (*from == keep_src || *from == keep_dst))
+        {
+            return false;
+        }
+
+        // Calculate the centerline before moving anything
+        let other = self.edges[remove.0].linestring.clone();
+        let mut centerline: Vec<Coord> = self.edges[keep.0]
+            .linestring
+            .0
+            .iter()
+            .map(|pt| match distance_to(&other, *pt) {
+                Some((_, closest)) => (*pt + closest.0) / 2.0,
+                None => *pt,
+            })
+            .collect();
+
+        for (to, from) in pairs {
+            let midpoint =
+                (self.intersections[to.0].point.0 + self.intersections[from.0].point.0) / 2.0;
+            if to != from {
+                let moved: Vec<EdgeID> = self.intersections[from.0]
+                    .edges
+                    .iter()
+                    .filter(|e| **e != remove)
+                    .cloned()
+                    .collect();
+                for e in moved {
+                    let edge = &mut self.edges[e.0];
+                    if edge.src == from {
+                        edge.src = to;
+                    }
+                    if edge.dst == from {
+                        edge.dst = to;
+                    }
+                    self.intersections[to.0].edges.push(e);
+                }
+                self.intersections[from.0].edges.retain(|e| *e == remove);
+            }
+            self.move_intersection(to, midpoint);
+        }
+
+        centerline[0] = self.intersections[self.edges[keep.0].src.0].point.0;
+        *centerline.last_mut().unwrap() = self.intersections[self.edges[keep.0].dst.0].point.0;
+
+        let removed_way = self.edges[remove.0].osm_way;
+        let removed_tags = self.edges[remove.0].osm_tags.clone();
+        let edge = &mut self.edges[keep.0];
+        edge.linestring = LineString::new(centerline);
+        edge.length_meters = edge.linestring.euclidean_length();
+        merge_tags(&mut edge.osm_tags, edge.direction, &removed_tags);
+        edge.direction = Direction::Both;
+        edge.merged_osm_ways.push(removed_way);
+        if let Some(relations) = self.edge_to_relations.get(&remove).cloned() {
+            let list = self.edge_to_relations.entry(keep).or_default();
+            for r in relations {
+                if !list.contains(&r) {
+                    list.push(r);
+                }
+            }
+        }
+        true
+    }
+
+    /// Move an intersection and the endpoints of all its edges
+    fn move_intersection(&mut self, i: IntersectionID, pt: Coord) {
+        self.intersections[i.0].point = Point(pt);
+        for e in self.intersections[i.0].edges.clone() {
+            let edge = &mut self.edges[e.0];
+            if edge.src == i {
+                edge.linestring.0[0] = pt;
+            }
+            if edge.dst == i {
+                *edge.linestring.0.last_mut().unwrap() = pt;
+            }
+            edge.length_meters = edge.linestring.euclidean_length();
+        }
+    }
+}
+
+/// Make the tags of a oneway edge, travelling in `direction`, describe both it and the opposite
+/// carriageway
+fn merge_tags(tags: &mut Tags, direction: Direction, other: &Tags) {
+    let has_lanes = |t: &Tags| t.has_any(["lanes", "lanes:forward", "lanes:backward"]);
+    if has_lanes(tags) || has_lanes(other) {
+        // Each side is oneway, so only one of these is nonzero
+        let lanes = tags.lane_counts();
+        let other_lanes = other.lane_counts();
+        let mut forward = lanes.forward + lanes.backward;
+        let mut backward = other_lanes.forward + other_lanes.backward;
+        if direction == Direction::Backward {
+            std::mem::swap(&mut forward, &mut backward);
+        }
+        tags.insert("lanes", (forward + backward + lanes.both_ways).to_string());
+        tags.insert("lanes:forward", forward.to_string());
+        tags.insert("lanes:backward", backward.to_string());
+    }
+    tags.insert("oneway", "no");
+}
+
+/// The distance from a point to a linestring, and the closest point on it
+fn distance_to(linestring: &LineString, pt: Coord) -> Option<(f64, Point)> {
+    match linestring.closest_point(&Point(pt)) {
+        Closest::Intersection(closest) | Closest::SinglePoint(closest) => {
+            Some(((closest.x() - pt.x).hypot(closest.y() - pt.y), closest))
+        }
+        Closest::Indeterminate => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use osm_reader::{NodeID, WayID};
+
+    use super::*;
+    use crate::osm2graph::Way;
+
+    fn oneway(id: i64, nodes: &[i64], name: &str) -> Way {
+        Way {
+            id: WayID(id),
+            node_ids: nodes.iter().map(|n| NodeID(*n)).collect(),
+            tags: Tags::from([("name", name), ("oneway", "yes"), ("lanes", "2")]),
+        }
+    }
+
+    /// Two oneway carriageways of Main Street, `gap` degrees of latitude apart and pointing in
+    /// `opposite` directions or not, with a side street attached to the end of the second
+    fn test_graph(gap: f64, opposite: bool) -> Graph {
+        let node_mapping = HashMap::from([
+            (NodeID(1), Coord { x: 0.0, y: 0.0 }),
+            (NodeID(2), Coord { x: 0.001, y: 0.0 }),
+            (NodeID(3), Coord { x: 0.001, y: gap }),
+            (NodeID(4), Coord { x: 0.0, y: gap }),
+            (NodeID(5), Coord { x: -0.001, y: gap }),
+        ]);
+        let second = if opposite { [3, 4] } else { [4, 3] };
+        let ways = vec![
+            oneway(10, &[1, 2], "Main Street"),
+            oneway(11, &second, "Main Street"),
+            Way {
+                id: WayID(12),
+                node_ids: vec![NodeID(4), NodeID(5)],
+                tags: Tags::empty(),
+            },
+        ];
+        Graph::from_scraped_osm(node_mapping, HashMap::new(), ways, |_| true)
+    }
+
+    #[test]
+    fn merge_opposite_carriageways() {
+        // About 10m apart
+        let mut graph = test_graph(0.00009, true);
+        graph.merge_dual_carriageways(15.0);
+        graph.validate().unwrap();
+
+        assert_eq!(graph.edges.len(), 2);
+        assert_eq!(graph.intersections.len(), 3);
+        let main = graph.edges.iter().find(|e| e.osm_way == WayID(10)).unwrap();
+        assert_eq!(main.direction, Direction::Both);
+        assert_eq!(main.merged_osm_ways, vec![WayID(11)]);
+        assert_eq!(Direction::from_osm(&main.osm_tags), Direction::Both);
+        let lanes = main.osm_tags.lane_counts();
+        assert_eq!((lanes.forward, lanes.backward), (2, 2));
+
+        // The side street is rewired to the start of the merged edge
+        let side = graph.edges.iter().find(|e| e.osm_way == WayID(12)).unwrap();
+        assert!(side.src == main.src || side.dst == main.src);
+    }
+
+    #[test]
+    fn dont_merge() {
+        // About 55m apart
+        let mut graph = test_graph(0.0005, true);
+        graph.merge_dual_carriageways(15.0);
+        assert_eq!(graph.edges.len(), 3);
+        assert!(graph.edges.iter().all(|e| e.merged_osm_ways.is_empty()));
+
+        // Close together, but both point the same way
+        let mut graph = test_graph(0.00009, false);
+        graph.merge_dual_carriageways(15.0);
+        assert_eq!(graph.edges.len(), 3);
+        assert!(graph
+            .edges
+            .iter()
+            .all(|e| e.direction == Direction::Forward || e.osm_way == WayID(12)));
+    }
+}
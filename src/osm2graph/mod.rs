@@ -0,0 +1,2275 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::OnceLock;
+
+use anyhow::{bail, Result};
+use geo::{
+    line_intersection::{line_intersection, LineIntersection},
+    Area, Closest, ClosestPoint, ConvexHull, Coord, EuclideanLength, HaversineLength, Intersects,
+    Line, LineLocatePoint, LineString, MultiPoint, Point, Polygon, Rect, SimplifyIdx,
+};
+use geojson::{FeatureCollection, GeoJson};
+use log::{info, warn};
+use osm_reader::{Element, NodeID, OsmID, RelationID, WayID};
+use rstar::{primitives::GeomWithData, Envelope, PointDistance, RTree, RTreeObject, AABB};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::tags::Oneway;
+use crate::{aabb, aabb_of, point_at_distance, Mercator, Tags};
+
+mod dual_carriageways;
+
+/// How far an intersection can be from the boundary to count for `Graph::boundary_edges`. This
+/// absorbs floating point error from clipping and projecting.
+pub const BOUNDARY_TOLERANCE_METERS: f64 = 1.0;
+
+/// Don't use this as a final structure, just an intermediate helper for splitting OSM ways into
+/// edges
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Graph {
+    pub edges: Vec<Edge>,
+    /// Nodes in the graph sense, not OSM, though they happen to correspond to one OSM node
+    // TODO Rename, but don't be confusing
+    pub intersections: Vec<Intersection>,
+    // All geometry is stored in world-space
+    pub mercator: Mercator,
+    pub boundary_polygon: Polygon,
+    /// Ways that aren't edges, but act as obstacles, like walls and fences. Only populated by
+    /// `Graph::new`.
+    pub barriers: Vec<(WayID, LineString, Tags)>,
+    /// For every edge that's part of a way in a relation (like a bus or cycle route), the
+    /// relations containing it. Populated by `Graph::new` or `index_relations`.
+    #[cfg_attr(feature = "serde", serde(with = "edge_map_as_pairs"))]
+    pub edge_to_relations: HashMap<EdgeID, Vec<RelationID>>,
+
+    /// Lazily built by `snap_to_edge` and `edges_in_bbox`. Methods changing edges reset it, but
+    /// callers editing `edges` directly must call `invalidate_edge_index`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    edge_snapper: OnceLock<RTree<GeomWithData<LineString, EdgeID>>>,
+    /// True only between `from_scraped_osm_wgs84` and `project`
+    #[cfg_attr(feature = "serde", serde(default))]
+    unprojected: bool,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub struct EdgeID(pub usize);
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub struct IntersectionID(pub usize);
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edge {
+    pub id: EdgeID,
+    pub src: IntersectionID,
+    pub dst: IntersectionID,
+
+    pub osm_way: osm_reader::WayID,
+    pub osm_node1: osm_reader::NodeID,
+    pub osm_node2: osm_reader::NodeID,
+    /// Every OSM node along the edge, in order. For edges from OSM, this matches the points of
+    /// `linestring`, until `densify_edges` adds points without a node.
+    pub osm_nodes: Vec<osm_reader::NodeID>,
+    pub osm_tags: Tags,
+    /// Which way traffic may travel along this edge, relative to `linestring`'s orientation
+    pub direction: Direction,
+
+    pub linestring: LineString,
+    /// The length of `linestring` in Mercator world-space. The projection is local, so this is
+    /// slightly distorted towards the edges of large imports.
+    pub length_meters: f64,
+    /// Other ways collapsed into this edge, like by `merge_dual_carriageways`
+    pub merged_osm_ways: Vec<WayID>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Only from `src` to `dst`
+    Forward,
+    /// Only from `dst` to `src`
+    Backward,
+    Both,
+}
+
+impl Direction {
+    /// Interpret `oneway`, `junction=roundabout`, and `highway=motorway`. Roundabouts and
+    /// motorways are oneway unless tagged `oneway=no`. Reversible ways change direction over
+    /// time, so they're treated as two-way.
+    pub fn from_osm(tags: &Tags) -> Self {
+        match tags.oneway() {
+            Oneway::Forward => Self::Forward,
+            Oneway::Backward => Self::Backward,
+            Oneway::No => Self::Both,
+        }
+    }
+}
+
+impl Edge {
+    /// Can this edge be traversed starting from the given intersection?
+    pub fn allows(&self, from: IntersectionID) -> bool {
+        match self.direction {
+            Direction::Forward => from == self.src,
+            Direction::Backward => from == self.dst,
+            Direction::Both => from == self.src || from == self.dst,
+        }
+    }
+
+    /// Identify this edge by OSM IDs, rather than its position in the graph. Unlike `id`, this
+    /// doesn't change when other ways are added or removed, so use it to match edges between two
+    /// imports. It's unique unless a way passes between the same two nodes more than once.
+    pub fn osm_key(&self) -> (WayID, NodeID, NodeID) {
+        (self.osm_way, self.osm_node1, self.osm_node2)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Intersection {
+    pub id: IntersectionID,
+    pub edges: Vec<EdgeID>,
+
+    pub osm_node: osm_reader::NodeID,
+    /// The tags of `osm_node`, or empty if it has none
+    pub osm_tags: Tags,
+    /// The OSM nodes of other intersections collapsed into this one, like by
+    /// `cluster_intersections`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub merged_osm_nodes: Vec<NodeID>,
+
+    pub point: Point,
+}
+
+/// A scraped OSM way
+pub struct Way {
+    pub id: WayID,
+    pub node_ids: Vec<NodeID>,
+    pub tags: Tags,
+}
+
+/// A scraped OSM relation, which can be collected through `OsmReader::relation`
+pub struct Relation {
+    pub id: RelationID,
+    /// (member, role) pairs
+    pub members: Vec<(OsmID, String)>,
+    pub tags: Tags,
+}
+
+/// A turn restriction from OSM, resolved to the graph
+#[derive(Clone, Debug, PartialEq)]
+pub struct TurnRestriction {
+    pub osm_relation: RelationID,
+    pub from: EdgeID,
+    pub via: IntersectionID,
+    pub to: EdgeID,
+    /// If true, `from` must lead to `to`. Otherwise, the turn from `from` to `to` is banned.
+    pub only: bool,
+    /// The raw value, like `no_left_turn` or `only_straight_on`
+    pub restriction: String,
+}
+
+/// Note this doesn't expose everything from osm_reader (version) and transforms some data
+pub trait OsmReader {
+    fn node(&mut self, id: osm_reader::NodeID, pt: Coord, tags: Tags);
+    fn way(
+        &mut self,
+        id: osm_reader::WayID,
+        node_ids: &Vec<osm_reader::NodeID>,
+        node_mapping: &HashMap<osm_reader::NodeID, Coord>,
+        tags: &Tags,
+    );
+    /// Called for every relation. `members` are (member, role) pairs. Does nothing by default.
+    fn relation(&mut self, _id: RelationID, _members: &[(OsmID, String)], _tags: &Tags) {}
+}
+
+/// Ignores everything
+pub struct NullReader;
+
+impl OsmReader for NullReader {
+    fn node(&mut self, _: osm_reader::NodeID, _: Coord, _: Tags) {}
+    fn way(
+        &mut self,
+        _: osm_reader::WayID,
+        _: &Vec<osm_reader::NodeID>,
+        _: &HashMap<osm_reader::NodeID, Coord>,
+        _: &Tags,
+    ) {
+    }
+}
+
+impl Graph {
+    /// Parse OSM data and split ways into edges. If `clip` is specified (in WGS84), ways crossing
+    /// it are truncated at the boundary, and ways entirely outside are dropped.
+    ///
+    /// Ways that aren't kept as edges, but match `keep_barrier`, are kept whole in `barriers`.
+    /// They aren't clipped, even if they cross or lie outside `clip`.
+    pub fn new<KeepEdge: Fn(&Tags) -> bool, KeepBarrier: Fn(&Tags) -> bool, R: OsmReader>(
+        input_bytes: &[u8],
+        keep_edge: KeepEdge,
+        keep_barrier: KeepBarrier,
+        reader: &mut R,
+        clip: Option<Polygon>,
+    ) -> Result<Self> {
+        info!("Parsing {} bytes of OSM data", input_bytes.len());
+
+        let mut scraper = Scraper::default();
+        osm_reader::parse(input_bytes, |elem| {
+            scraper.handle(elem, &keep_edge, &keep_barrier, reader)
+        })?;
+        Ok(scraper.finish(clip))
+    }
+
+    /// Like `new`, but for OSM elements that were already parsed elsewhere. Elements must be in
+    /// the usual order, with nodes before the ways referring to them.
+    pub fn from_elements<
+        KeepEdge: Fn(&Tags) -> bool,
+        KeepBarrier: Fn(&Tags) -> bool,
+        R: OsmReader,
+    >(
+        elements: impl Iterator<Item = Element>,
+        keep_edge: KeepEdge,
+        keep_barrier: KeepBarrier,
+        reader: &mut R,
+        clip: Option<Polygon>,
+    ) -> Self {
+        let mut scraper = Scraper::default();
+        for elem in elements {
+            scraper.handle(elem, &keep_edge, &keep_barrier, reader);
+        }
+        scraper.finish(clip)
+    }
+
+    /// Split ways into edges. The boundary is the convex hull of everything.
+    ///
+    /// Right after splitting, while geometry is still in WGS84, `edge_filter` is called on every
+    /// edge. It can modify the edge, and returning false removes it, along with any intersections
+    /// left without edges. Pass `|_| true` to keep everything.
+    pub fn from_scraped_osm<F: FnMut(&mut Edge) -> bool>(
+        node_mapping: HashMap<NodeID, Coord>,
+        node_tags: HashMap<NodeID, Tags>,
+        ways: Vec<Way>,
+        mut edge_filter: F,
+    ) -> Self {
+        let boundary = MultiPoint::from(
+            ways.iter()
+                .flat_map(|w| w.node_ids.iter().map(|n| Point(node_mapping[n])))
+                .collect::<Vec<_>>(),
+        )
+        .convex_hull();
+        let mut graph = Self::from_scraped_osm_wgs84(node_mapping, node_tags, ways, boundary);
+        let remove: HashSet<EdgeID> = graph
+            .edges
+            .iter_mut()
+            .filter_map(|e| (!edge_filter(e)).then_some(e.id))
+            .collect();
+        if !remove.is_empty() {
+            graph.remove_edges(remove);
+        }
+        graph.project();
+        graph
+    }
+
+    /// Split ways into edges, using a boundary polygon in WGS84. Ways entirely outside the
+    /// boundary are dropped, but ways partly inside are kept whole.
+    pub fn from_scraped_osm_with_boundary(
+        node_mapping: HashMap<NodeID, Coord>,
+        node_tags: HashMap<NodeID, Tags>,
+        ways: Vec<Way>,
+        boundary: Polygon,
+    ) -> Self {
+        let mut graph = Self::from_scraped_osm_wgs84(node_mapping, node_tags, ways, boundary);
+        graph.project();
+        graph
+    }
+
+    /// Like `from_scraped_osm_with_boundary`, but all geometry is left in WGS84, and
+    /// `length_meters` is the geodesic (haversine) length. `mercator` is calculated, but not
+    /// applied yet. Call `project` before using anything that expects world-space.
+    pub fn from_scraped_osm_wgs84(
+        node_mapping: HashMap<NodeID, Coord>,
+        node_tags: HashMap<NodeID, Tags>,
+        mut ways: Vec<Way>,
+        boundary: Polygon,
+    ) -> Self {
+        ways.retain(|w| {
+            let linestring: LineString = w.node_ids.iter().map(|n| node_mapping[n]).collect();
+            linestring.intersects(&boundary)
+        });
+
+        info!("Splitting {} ways into edges", ways.len());
+        let (mut edges, intersections) = split_edges(node_mapping, node_tags, ways);
+
+        // Intersections are always at the ends of edges, so they don't affect the bounds
+        let bounds = aabb_of(edges.iter().map(|e| &e.linestring))
+            .map(|bounds| bounds.merged(&aabb(&boundary)))
+            .unwrap_or_else(|| aabb(&boundary));
+        let mercator = Mercator::new_from_bounds(Rect::new(bounds.lower().0, bounds.upper().0));
+        for e in &mut edges {
+            e.length_meters = e.linestring.haversine_length();
+        }
+
+        Self {
+            edges,
+            intersections,
+            mercator,
+            boundary_polygon: boundary,
+            barriers: Vec::new(),
+            edge_to_relations: HashMap::new(),
+            edge_snapper: OnceLock::new(),
+            unprojected: true,
+        }
+    }
+
+    /// Project all geometry from WGS84 to world-space using `mercator`, and recalculate
+    /// `length_meters` in world-space. Only for graphs from `from_scraped_osm_wgs84`; every other
+    /// constructor has already done this. Calling this on a graph that's already projected does
+    /// nothing.
+    pub fn project(&mut self) {
+        if !self.unprojected {
+            warn!("Graph::project called on a graph that's already projected, ignoring");
+            return;
+        }
+        self.unprojected = false;
+        for e in &mut self.edges {
+            self.mercator.to_mercator_in_place(&mut e.linestring);
+            e.length_meters = e.linestring.euclidean_length();
+        }
+        for i in &mut self.intersections {
+            self.mercator.to_mercator_in_place(&mut i.point);
+        }
+        self.mercator
+            .to_mercator_in_place(&mut self.boundary_polygon);
+        for (_, linestring, _) in &mut self.barriers {
+            self.mercator.to_mercator_in_place(linestring);
+        }
+        self.invalidate_edge_index();
+    }
+
+    /// Remove some edges, and any intersections left without edges. IDs are renumbered to stay
+    /// contiguous, so any `EdgeID` or `IntersectionID` held from before is invalid afterwards.
+    pub fn remove_edges(&mut self, remove: impl IntoIterator<Item = EdgeID>) {
+        self.remove_edges_returning(remove);
+    }
+
+    /// Like `remove_edges`, but returns the removed edges and intersections in their original
+    /// order, so they can be restored later, like for undo. They keep their IDs from before
+    /// renumbering, and a removed intersection still lists all of its original edges.
+    pub fn remove_edges_returning(
+        &mut self,
+        remove: impl IntoIterator<Item = EdgeID>,
+    ) -> (Vec<Edge>, Vec<Intersection>) {
+        let remove: HashSet<EdgeID> = remove.into_iter().collect();
+        let (removed_edges, kept_edges): (Vec<Edge>, Vec<Edge>) = std::mem::take(&mut self.edges)
+            .into_iter()
+            .partition(|e| remove.contains(&e.id));
+        self.edges = kept_edges;
+        let mut edge_mapping = HashMap::new();
+        for (idx, e) in self.edges.iter_mut().enumerate() {
+            edge_mapping.insert(e.id, EdgeID(idx));
+            e.id = EdgeID(idx);
+        }
+
+        let mut removed_intersections = Vec::new();
+        for i in std::mem::take(&mut self.intersections) {
+            let edges: Vec<EdgeID> = i
+                .edges
+                .iter()
+                .filter_map(|e| edge_mapping.get(e).cloned())
+                .collect();
+            if edges.is_empty() {
+                removed_intersections.push(i);
+            } else {
+                self.intersections.push(Intersection { edges, ..i });
+            }
+        }
+        let mut intersection_mapping = HashMap::new();
+        for (idx, i) in self.intersections.iter_mut().enumerate() {
+            intersection_mapping.insert(i.id, IntersectionID(idx));
+            i.id = IntersectionID(idx);
+        }
+
+        for e in &mut self.edges {
+            e.src = intersection_mapping[&e.src];
+            e.dst = intersection_mapping[&e.dst];
+        }
+
+        self.edge_to_relations = std::mem::take(&mut self.edge_to_relations)
+            .into_iter()
+            .filter_map(|(e, relations)| Some((*edge_mapping.get(&e)?, relations)))
+            .collect();
+        self.invalidate_edge_index();
+
+        (removed_edges, removed_intersections)
+    }
+
+    /// Replace `edge_to_relations`, matching the way members of each relation to the edges those
+    /// ways produced, including ways merged into an edge.
+    pub fn index_relations(&mut self, relations: &[Relation]) {
+        let mut way_to_relations: HashMap<WayID, Vec<RelationID>> = HashMap::new();
+        for relation in relations {
+            for (member, _) in &relation.members {
+                if let OsmID::Way(way) = member {
+                    let list = way_to_relations.entry(*way).or_default();
+                    if !list.contains(&relation.id) {
+                        list.push(relation.id);
+                    }
+                }
+            }
+        }
+
+        self.edge_to_relations.clear();
+        for e in &self.edges {
+            let mut list = Vec::new();
+            for way in std::iter::once(&e.osm_way).chain(&e.merged_osm_ways) {
+                for r in way_to_relations.get(way).into_iter().flatten() {
+                    if !list.contains(r) {
+                        list.push(*r);
+                    }
+                }
+            }
+            if !list.is_empty() {
+                self.edge_to_relations.insert(e.id, list);
+            }
+        }
+    }
+
+    /// Check that edges and intersections are consistent with each other, returning an error
+    /// describing the first problem found. Useful in tests after editing the graph.
+    pub fn validate(&self) -> Result<()> {
+        for (idx, e) in self.edges.iter().enumerate() {
+            if e.id != EdgeID(idx) {
+                bail!("Edge at index {idx} has ID {:?}", e.id);
+            }
+            for i in [e.src, e.dst] {
+                let Some(intersection) = self.intersections.get(i.0) else {
+                    bail!("{:?} points to missing {i:?}", e.id);
+                };
+                if !intersection.edges.contains(&e.id) {
+                    bail!("{:?} points to {i:?}, but isn't listed there", e.id);
+                }
+            }
+        }
+        for (idx, i) in self.intersections.iter().enumerate() {
+            if i.id != IntersectionID(idx) {
+                bail!("Intersection at index {idx} has ID {:?}", i.id);
+            }
+            if i.edges.is_empty() {
+                bail!("{:?} has no edges", i.id);
+            }
+            for e in &i.edges {
+                let Some(edge) = self.edges.get(e.0) else {
+                    bail!("{:?} lists missing {e:?}", i.id);
+                };
+                if edge.src != i.id && edge.dst != i.id {
+                    bail!("{:?} lists {e:?}, which doesn't point back", i.id);
+                }
+            }
+        }
+        for e in self.edge_to_relations.keys() {
+            if e.0 >= self.edges.len() {
+                bail!("edge_to_relations refers to missing {e:?}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a new intersection, in world-space. It has no edges yet. Since it doesn't come from
+    /// OSM, it gets a new negative `osm_node`.
+    pub fn add_intersection(&mut self, point: Point) -> IntersectionID {
+        let id = IntersectionID(self.intersections.len());
+        let osm_node = NodeID(
+            self.intersections
+                .iter()
+                .map(|i| i.osm_node.0)
+                .min()
+                .unwrap_or(0)
+                .min(0)
+                - 1,
+        );
+        self.intersections.push(Intersection {
+            id,
+            edges: Vec::new(),
+            osm_node,
+            osm_tags: Tags::empty(),
+            merged_osm_nodes: Vec::new(),
+            point,
+        });
+        id
+    }
+
+    /// Add a new edge between two intersections. The linestring is in world-space and should
+    /// start and end at the intersections. Since it doesn't come from OSM, it gets a new negative
+    /// `osm_way`, and `osm_node1` and `osm_node2` are just the intersections' nodes. `osm_nodes`
+    /// only contains those two nodes.
+    pub fn add_edge(
+        &mut self,
+        src: IntersectionID,
+        dst: IntersectionID,
+        linestring: LineString,
+        tags: Tags,
+    ) -> EdgeID {
+        let id = EdgeID(self.edges.len());
+        let osm_way = WayID(
+            self.edges
+                .iter()
+                .map(|e| e.osm_way.0)
+                .min()
+                .unwrap_or(0)
+                .min(0)
+                - 1,
+        );
+        self.edges.push(Edge {
+            id,
+            src,
+            dst,
+            osm_way,
+            osm_node1: self.intersections[src.0].osm_node,
+            osm_node2: self.intersections[dst.0].osm_node,
+            osm_nodes: vec![
+                self.intersections[src.0].osm_node,
+                self.intersections[dst.0].osm_node,
+            ],
+            direction: Direction::from_osm(&tags),
+            osm_tags: tags,
+            length_meters: linestring.euclidean_length(),
+            linestring,
+            merged_osm_ways: Vec::new(),
+        });
+        // Like split_edges, a self-loop is listed twice
+        self.intersections[src.0].edges.push(id);
+        self.intersections[dst.0].edges.push(id);
+        self.invalidate_edge_index();
+        id
+    }
+
+    /// Find the edges containing an OSM node. If the node became an intersection, or was merged
+    /// into one, this is every edge touching it. Otherwise it's the edge with the node in its
+    /// interior, if any.
+    pub fn edges_at_osm_node(&self, node: NodeID) -> Vec<EdgeID> {
+        if let Some(i) = self
+            .intersections
+            .iter()
+            .find(|i| i.osm_node == node || i.merged_osm_nodes.contains(&node))
+        {
+            let mut edges = i.edges.clone();
+            // Self-loops are listed twice
+            edges.sort();
+            edges.dedup();
+            return edges;
+        }
+        self.edges
+            .iter()
+            .filter(|e| e.osm_nodes.contains(&node))
+            .map(|e| e.id)
+            .collect()
+    }
+
+    /// The OSM ways of all edges touching an intersection
+    pub fn ways_at_intersection(&self, i: IntersectionID) -> BTreeSet<WayID> {
+        self.intersections[i.0]
+            .edges
+            .iter()
+            .map(|e| self.edges[e.0].osm_way)
+            .collect()
+    }
+
+    /// All edges touching an intersection, paired with the intersection on the other end. A
+    /// self-loop is returned twice, pointing back to `i`.
+    pub fn neighbors(&self, i: IntersectionID) -> Vec<(EdgeID, IntersectionID)> {
+        self.intersections[i.0]
+            .edges
+            .iter()
+            .map(|e| {
+                let edge = &self.edges[e.0];
+                (*e, if edge.src == i { edge.dst } else { edge.src })
+            })
+            .collect()
+    }
+
+    /// The number of edge endpoints at an intersection. Self-loops count twice.
+    pub fn degree(&self, i: IntersectionID) -> usize {
+        // split_edges and add_edge already list self-loops twice
+        self.intersections[i.0].edges.len()
+    }
+
+    /// A small polygon around an intersection, for drawing a junction. It's the convex hull of
+    /// each incident edge's first `radius_meters`, widened to `radius_meters` across. Returns
+    /// `None` for an intersection without edges or with zero-length edges.
+    pub fn intersection_polygon(&self, i: IntersectionID, radius_meters: f64) -> Option<Polygon> {
+        let center = self.intersections[i.0].point;
+        let mut edges = self.intersections[i.0].edges.clone();
+        // Self-loops are listed twice, but both ends are handled below
+        edges.sort();
+        edges.dedup();
+
+        let mut points = vec![center];
+        for e in edges {
+            let edge = &self.edges[e.0];
+            let mut ends = Vec::new();
+            if edge.src == i {
+                ends.push(edge.linestring.clone());
+            }
+            if edge.dst == i {
+                let mut reversed = edge.linestring.clone();
+                reversed.0.reverse();
+                ends.push(reversed);
+            }
+
+            for linestring in ends {
+                let dist = radius_meters.min(linestring.euclidean_length());
+                let Some(cut) = point_at_distance(&linestring, dist) else {
+                    continue;
+                };
+                // Include any vertices before the cut, for curved edges
+                let mut dist_along = 0.0;
+                for line in linestring.lines() {
+                    dist_along += line.euclidean_length();
+                    if dist_along >= dist {
+                        break;
+                    }
+                    points.push(Point(line.end));
+                }
+
+                let direction = cut.0 - center.0;
+                let length = direction.x.hypot(direction.y);
+                if length == 0.0 {
+                    continue;
+                }
+                let perpendicular = Coord {
+                    x: -direction.y,
+                    y: direction.x,
+                } / length
+                    * (radius_meters / 2.0);
+                points.push(Point(cut.0 + perpendicular));
+                points.push(Point(cut.0 - perpendicular));
+            }
+        }
+
+        let hull = MultiPoint::from(points).convex_hull();
+        if hull.unsigned_area() == 0.0 {
+            return None;
+        }
+        Some(hull)
+    }
+
+    /// All intersections with exactly one edge
+    pub fn dead_ends(&self) -> Vec<IntersectionID> {
+        self.intersections
+            .iter()
+            .filter(|i| self.degree(i.id) == 1)
+            .map(|i| i.id)
+            .collect()
+    }
+
+    /// Merge intersections within `radius_meters` of each other into one at their centroid. This
+    /// chains, so a cluster may be wider than the radius. The merged intersection keeps the
+    /// smallest ID and its `osm_node`, recording the others in `merged_osm_nodes`. Edges are
+    /// rewired to it, moving their endpoints, but keep their original `osm_nodes`. Edges
+    /// inside a cluster become self-loops, unless they lie entirely within `radius_meters` of the
+    /// new point, in which case they're removed. IDs are renumbered afterwards, like
+    /// `remove_edges`.
+    pub fn cluster_intersections(&mut self, radius_meters: f64) {
+        let tree = RTree::bulk_load(
+            self.intersections
+                .iter()
+                .map(|i| GeomWithData::new([i.point.x(), i.point.y()], i.id.0))
+                .collect(),
+        );
+        let mut parent: Vec<usize> = (0..self.intersections.len()).collect();
+        for i in &self.intersections {
+            for other in
+                tree.locate_within_distance([i.point.x(), i.point.y()], radius_meters.powi(2))
+            {
+                let root1 = find(&mut parent, i.id.0);
+                let root2 = find(&mut parent, other.data);
+                // The smallest index stays the root
+                if root1 != root2 {
+                    parent[root1.max(root2)] = root1.min(root2);
+                }
+            }
+        }
+
+        let mut clusters: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for idx in 0..self.intersections.len() {
+            let root = find(&mut parent, idx);
+            clusters.entry(root).or_default().push(idx);
+        }
+        let mut merged_into = HashMap::new();
+        for (root, members) in clusters {
+            if members.len() == 1 {
+                continue;
+            }
+            let centroid = members.iter().fold(Coord::zero(), |sum, idx| {
+                sum + self.intersections[*idx].point.0
+            }) / members.len() as f64;
+            self.intersections[root].point = Point(centroid);
+            for idx in members {
+                merged_into.insert(IntersectionID(idx), IntersectionID(root));
+                if idx != root {
+                    let edges = std::mem::take(&mut self.intersections[idx].edges);
+                    let mut nodes = std::mem::take(&mut self.intersections[idx].merged_osm_nodes);
+                    nodes.insert(0, self.intersections[idx].osm_node);
+                    let root = &mut self.intersections[root];
+                    root.edges.extend(edges);
+                    root.merged_osm_nodes.extend(nodes);
+                }
+            }
+        }
+        if merged_into.is_empty() {
+            return;
+        }
+
+        let mut remove = HashSet::new();
+        for e in &mut self.edges {
+            if let Some(to) = merged_into.get(&e.src) {
+                e.src = *to;
+                e.linestring.0[0] = self.intersections[to.0].point.0;
+            }
+            if let Some(to) = merged_into.get(&e.dst) {
+                e.dst = *to;
+                *e.linestring.0.last_mut().unwrap() = self.intersections[to.0].point.0;
+            }
+            e.length_meters = e.linestring.euclidean_length();
+
+            if e.src == e.dst && merged_into.contains_key(&e.src) {
+                let center = self.intersections[e.src.0].point;
+                if e.linestring
+                    .points()
+                    .all(|pt| (pt.x() - center.x()).hypot(pt.y() - center.y()) <= radius_meters)
+                {
+                    remove.insert(e.id);
+                }
+            }
+        }
+        info!(
+            "Clustered {} intersections, removing {} edges",
+            merged_into.len(),
+            remove.len()
+        );
+        self.remove_edges(remove);
+    }
+
+    /// Repeatedly remove paths leading to a dead-end that're at most `max_length_meters` long. A
+    /// path is the chain of edges from a dead-end through intersections with exactly two edges,
+    /// until the next junction or dead-end. The whole chain's length counts, so a long cul-de-sac
+    /// split into many short edges is kept. This cleans up short stubs, and repeating catches
+    /// branching stubs. IDs are renumbered afterwards, like `remove_edges`.
+    pub fn prune_dead_end_paths(&mut self, max_length_meters: f64) {
+        loop {
+            let mut remove = HashSet::new();
+            for i in self.dead_ends() {
+                if let Some(chain) = self.short_dead_end_chain(i, max_length_meters) {
+                    remove.extend(chain);
+                }
+            }
+            if remove.is_empty() {
+                return;
+            }
+            self.remove_edges(remove);
+        }
+    }
+
+    /// Walk from a dead-end through intersections with two edges. If the total length is at most
+    /// `max_length_meters`, returns the edges along the way.
+    fn short_dead_end_chain(
+        &self,
+        dead_end: IntersectionID,
+        max_length_meters: f64,
+    ) -> Option<Vec<EdgeID>> {
+        let mut chain = Vec::new();
+        let mut length = 0.0;
+        let mut current = dead_end;
+        let mut edge = self.intersections[dead_end.0].edges[0];
+        loop {
+            chain.push(edge);
+            length += self.edges[edge.0].length_meters;
+            if length > max_length_meters {
+                return None;
+            }
+            let e = &self.edges[edge.0];
+            current = if e.src == current { e.dst } else { e.src };
+            if self.degree(current) != 2 {
+                return Some(chain);
+            }
+            edge = *self.intersections[current.0]
+                .edges
+                .iter()
+                .find(|e| **e != edge)?;
+        }
+    }
+
+    /// Find groups of multiple edges connecting the same two intersections, regardless of
+    /// direction. Self-loops aren't included; see `self_loops` for those.
+    pub fn parallel_edges(&self) -> Vec<Vec<EdgeID>> {
+        let mut groups: BTreeMap<(IntersectionID, IntersectionID), Vec<EdgeID>> = BTreeMap::new();
+        for e in &self.edges {
+            if e.src == e.dst {
+                continue;
+            }
+            let key = (e.src.min(e.dst), e.src.max(e.dst));
+            groups.entry(key).or_default().push(e.id);
+        }
+        groups.into_values().filter(|g| g.len() > 1).collect()
+    }
+
+    /// Find all edges starting and ending at the same intersection
+    pub fn self_loops(&self) -> Vec<EdgeID> {
+        self.edges
+            .iter()
+            .filter(|e| e.src == e.dst)
+            .map(|e| e.id)
+            .collect()
+    }
+
+    /// All edges, sorted by `Edge::osm_key`. Unlike `edges`, this order doesn't depend on
+    /// how the graph was built or modified, so it's useful for producing reproducible output.
+    pub fn edges_in_osm_order(&self) -> Vec<&Edge> {
+        let mut edges: Vec<&Edge> = self.edges.iter().collect();
+        edges.sort_by_key(|e| (e.osm_way.0, e.osm_node1.0, e.osm_node2.0, e.id));
+        edges
+    }
+
+    /// Renumber all edges and intersections in a deterministic order derived from OSM. Edges are
+    /// sorted by `Edge::osm_key` and intersections by `osm_node`, so importing the same OSM data
+    /// always produces the same IDs, regardless of input order.
+    ///
+    /// The IDs are still positions, so they're only reproducible for identical input. Adding or
+    /// removing one way shifts every later ID, so don't compare them across OSM versions. To diff
+    /// two imports, match edges by `Edge::osm_key` and intersections by `osm_node` instead.
+    pub fn assign_osm_stable_ids(&mut self) {
+        let mut edges = std::mem::take(&mut self.edges);
+        edges.sort_by_key(|e| (e.osm_way.0, e.osm_node1.0, e.osm_node2.0, e.id));
+        let mut intersections = std::mem::take(&mut self.intersections);
+        intersections.sort_by_key(|i| (i.osm_node.0, i.id));
+
+        let edge_mapping: HashMap<EdgeID, EdgeID> = edges
+            .iter()
+            .enumerate()
+            .map(|(idx, e)| (e.id, EdgeID(idx)))
+            .collect();
+        let intersection_mapping: HashMap<IntersectionID, IntersectionID> = intersections
+            .iter()
+            .enumerate()
+            .map(|(idx, i)| (i.id, IntersectionID(idx)))
+            .collect();
+
+        for e in &mut edges {
+            e.id = edge_mapping[&e.id];
+            e.src = intersection_mapping[&e.src];
+            e.dst = intersection_mapping[&e.dst];
+        }
+        for i in &mut intersections {
+            i.id = intersection_mapping[&i.id];
+            for e in &mut i.edges {
+                *e = edge_mapping[e];
+            }
+        }
+
+        self.edges = edges;
+        self.intersections = intersections;
+        self.edge_to_relations = std::mem::take(&mut self.edge_to_relations)
+            .into_iter()
+            .map(|(e, relations)| (edge_mapping[&e], relations))
+            .collect();
+        self.invalidate_edge_index();
+    }
+
+    /// Group intersections into connected components, ignoring edge direction. The largest
+    /// component is first.
+    pub fn connected_components(&self) -> Vec<BTreeSet<IntersectionID>> {
+        // Union-find over intersection indices
+        let mut parent: Vec<usize> = (0..self.intersections.len()).collect();
+        for e in &self.edges {
+            let root1 = find(&mut parent, e.src.0);
+            let root2 = find(&mut parent, e.dst.0);
+            if root1 != root2 {
+                parent[root1] = root2;
+            }
+        }
+
+        let mut components: HashMap<usize, BTreeSet<IntersectionID>> = HashMap::new();
+        for i in &self.intersections {
+            let root = find(&mut parent, i.id.0);
+            components.entry(root).or_default().insert(i.id);
+        }
+        let mut components: Vec<_> = components.into_values().collect();
+        // Break ties deterministically
+        components.sort_by_key(|c| (std::cmp::Reverse(c.len()), c.first().cloned()));
+        components
+    }
+
+    /// Remove all edges and intersections not part of the largest connected component
+    pub fn retain_largest_component(&mut self) {
+        let Some(keep) = self.connected_components().into_iter().next() else {
+            return;
+        };
+        let remove: Vec<EdgeID> = self
+            .edges
+            .iter()
+            .filter(|e| !keep.contains(&e.src))
+            .map(|e| e.id)
+            .collect();
+        self.remove_edges(remove);
+    }
+
+    /// Simplify every edge's linestring with Ramer-Douglas-Peucker. The endpoints are always
+    /// preserved. `osm_nodes` is filtered to match.
+    pub fn simplify_edges(&mut self, epsilon_meters: f64) {
+        let mut before = 0;
+        let mut after = 0;
+        for e in &mut self.edges {
+            before += e.linestring.0.len();
+            let keep = e.linestring.simplify_idx(&epsilon_meters);
+            if e.osm_nodes.len() == e.linestring.0.len() {
+                e.osm_nodes = keep.iter().map(|idx| e.osm_nodes[*idx]).collect();
+            }
+            e.linestring = keep.iter().map(|idx| e.linestring.0[*idx]).collect();
+            e.length_meters = e.linestring.euclidean_length();
+            after += e.linestring.0.len();
+        }
+        info!("Simplifying edges reduced {before} points to {after}");
+        self.invalidate_edge_index();
+    }
+
+    /// Add points to every edge's linestring, so no segment is longer than
+    /// `max_segment_meters`. Existing points aren't moved, and long segments are split evenly.
+    /// `osm_nodes` isn't changed, so it still lists the original nodes, but no longer matches the
+    /// points of `linestring`.
+    pub fn densify_edges(&mut self, max_segment_meters: f64) {
+        if max_segment_meters <= 0.0 {
+            return;
+        }
+        let mut before = 0;
+        let mut after = 0;
+        for e in &mut self.edges {
+            before += e.linestring.0.len();
+            let mut pts = Vec::new();
+            for line in e.linestring.lines() {
+                pts.push(line.start);
+                let pieces = (line.euclidean_length() / max_segment_meters).ceil() as usize;
+                for i in 1..pieces {
+                    let fraction = i as f64 / pieces as f64;
+                    pts.push(line.start + (line.end - line.start) * fraction);
+                }
+            }
+            pts.extend(e.linestring.0.last().cloned());
+            e.linestring = LineString::new(pts);
+            after += e.linestring.0.len();
+        }
+        info!("Densifying edges increased {before} points to {after}");
+        self.invalidate_edge_index();
+    }
+
+    /// The sum of all edge lengths
+    pub fn total_length_meters(&self) -> f64 {
+        self.edges.iter().map(|e| e.length_meters).sum()
+    }
+
+    /// Dump every edge and intersection as GeoJSON in WGS84, for debugging. Edges have
+    /// `edge_id`, `osm_way`, and all OSM tags as properties; intersections have
+    /// `intersection_id` and `osm_node`.
+    pub fn to_geojson(&self) -> String {
+        let mut features = Vec::new();
+        for e in &self.edges {
+            let mut f = self.mercator.to_wgs84_gj(&e.linestring);
+            for (k, v) in &e.osm_tags.0 {
+                f.set_property(k.clone(), v.clone());
+            }
+            f.set_property("edge_id", e.id.0);
+            f.set_property("osm_way", e.osm_way.0);
+            features.push(f);
+        }
+        for i in &self.intersections {
+            let mut f = self.mercator.to_wgs84_gj(&i.point);
+            f.set_property("intersection_id", i.id.0);
+            f.set_property("osm_node", i.osm_node.0);
+            features.push(f);
+        }
+        GeoJson::from(FeatureCollection {
+            features,
+            bbox: None,
+            foreign_members: None,
+        })
+        .to_string()
+    }
+
+    /// Find the edge closest to a point in world-space, and the fraction along its linestring
+    /// closest to that point. Returns `None` for an empty graph. The first call builds a spatial
+    /// index; later calls are cheap.
+    ///
+    /// The index is reset by every method here that changes edges, but not when `edges` or an
+    /// edge's `linestring` is modified directly. After doing that, call `invalidate_edge_index`,
+    /// or the result may be a wrong or even out-of-bounds `EdgeID`.
+    pub fn snap_to_edge(&self, pt: Point) -> Option<(EdgeID, f64)> {
+        let obj = self.edge_snapper().nearest_neighbor(&pt)?;
+        let fraction = obj.geom().line_locate_point(&pt)?;
+        Some((obj.data, fraction))
+    }
+
+    /// Like `snap_to_edge`, but the input is in WGS84
+    pub fn snap_wgs84_to_edge(&self, pt: Point) -> Option<(EdgeID, f64)> {
+        self.snap_to_edge(self.mercator.to_mercator(&pt))
+    }
+
+    /// Find all edges whose bounding box intersects the given one, in world-space. Like
+    /// `snap_to_edge`, this reuses a lazily built spatial index, with the same caveat about
+    /// modifying edges directly.
+    pub fn edges_in_bbox(&self, aabb: AABB<Point>) -> Vec<EdgeID> {
+        self.edge_snapper()
+            .locate_in_envelope_intersecting(&aabb)
+            .map(|obj| obj.data)
+            .collect()
+    }
+
+    /// The world-space bounding box of the study area, from the origin to the projection's
+    /// width and height
+    pub fn mercator_bounds(&self) -> Rect {
+        Rect::new(
+            Coord { x: 0.0, y: 0.0 },
+            Coord {
+                x: self.mercator.width,
+                y: self.mercator.height,
+            },
+        )
+    }
+
+    /// The bounding box of the study area in WGS84, by unprojecting `mercator_bounds`
+    pub fn wgs84_bounds(&self) -> Rect {
+        self.mercator.to_wgs84_rect(self.mercator_bounds())
+    }
+
+    /// Assign every edge to the square grid cells of size `cell_meters` that its bounding box
+    /// overlaps. Cell `(x, y)` covers world-space `[x * cell_meters, (x + 1) * cell_meters)`, and
+    /// likewise for y. An edge appears in every cell its bounding box touches, so a long or
+    /// diagonal edge is listed in many cells, including ones its linestring doesn't actually
+    /// cross. This is a cheaper alternative to `edges_in_bbox` for a broad-phase search, when
+    /// building an R-tree is too slow.
+    ///
+    /// # Panics
+    ///
+    /// If `cell_meters` isn't a positive, finite number. NaN and infinity are rejected.
+    pub fn bucket_edges(&self, cell_meters: f64) -> HashMap<(i32, i32), Vec<EdgeID>> {
+        assert!(
+            cell_meters.is_finite() && cell_meters > 0.0,
+            "bucket_edges needs a positive, finite cell size, not {cell_meters}"
+        );
+        let cell = |value: f64| (value / cell_meters).floor() as i32;
+        let mut buckets: HashMap<(i32, i32), Vec<EdgeID>> = HashMap::new();
+        for e in &self.edges {
+            let bbox = aabb(&e.linestring);
+            let (min, max) = (bbox.lower(), bbox.upper());
+            for x in cell(min.x())..=cell(max.x()) {
+                for y in cell(min.y())..=cell(max.y()) {
+                    buckets.entry((x, y)).or_default().push(e.id);
+                }
+            }
+        }
+        buckets
+    }
+
+    /// Find edges with an endpoint on the exterior of `boundary_polygon`, within
+    /// `BOUNDARY_TOLERANCE_METERS`. These are the "portals" where trips can enter or leave the
+    /// imported area. A custom boundary from `Graph::new`'s `clip` produces endpoints on the
+    /// boundary; with the default convex hull, only edges reaching the outermost points count.
+    pub fn boundary_edges(&self) -> Vec<EdgeID> {
+        let exterior = self.boundary_polygon.exterior();
+        let on_boundary: HashSet<IntersectionID> = self
+            .intersections
+            .iter()
+            .filter(|i| match exterior.closest_point(&i.point) {
+                Closest::Intersection(pt) | Closest::SinglePoint(pt) => {
+                    (pt.x() - i.point.x()).hypot(pt.y() - i.point.y()) <= BOUNDARY_TOLERANCE_METERS
+                }
+                Closest::Indeterminate => false,
+            })
+            .map(|i| i.id)
+            .collect();
+        self.edges
+            .iter()
+            .filter(|e| on_boundary.contains(&e.src) || on_boundary.contains(&e.dst))
+            .map(|e| e.id)
+            .collect()
+    }
+
+    /// Find pairs of edges whose linestrings cross, but don't share an intersection. Usually these
+    /// are bridges or tunnels, but without `layer` tags, they may be missing OSM nodes. Each pair
+    /// is reported once with the smaller ID first, along with the first crossing point found.
+    pub fn find_geometric_crossings_without_nodes(&self) -> Vec<(EdgeID, EdgeID, Point)> {
+        let mut results = Vec::new();
+        for e1 in &self.edges {
+            for id2 in self.edges_in_bbox(aabb(&e1.linestring)) {
+                if id2 <= e1.id {
+                    continue;
+                }
+                let e2 = &self.edges[id2.0];
+                if [e1.src, e1.dst]
+                    .iter()
+                    .any(|i| *i == e2.src || *i == e2.dst)
+                {
+                    continue;
+                }
+                if let Some(pt) = first_crossing(&e1.linestring, &e2.linestring) {
+                    results.push((e1.id, e2.id, pt));
+                }
+            }
+        }
+        results.sort_by_key(|(e1, e2, _)| (*e1, *e2));
+        results
+    }
+
+    /// Build an RTree over the bounding box of every edge. The caller owns the result, so it can
+    /// be built once and queried many times.
+    pub fn build_edge_index(&self) -> RTree<EdgeRef> {
+        RTree::bulk_load(
+            self.edges
+                .iter()
+                .map(|e| EdgeRef {
+                    id: e.id,
+                    aabb: aabb(&e.linestring),
+                })
+                .collect(),
+        )
+    }
+
+    /// Throw away the spatial index used by `snap_to_edge` and `edges_in_bbox`, so it's rebuilt on
+    /// the next query. Call this after modifying `edges` directly.
+    pub fn invalidate_edge_index(&mut self) {
+        self.edge_snapper = OnceLock::new();
+    }
+
+    fn edge_snapper(&self) -> &RTree<GeomWithData<LineString, EdgeID>> {
+        self.edge_snapper.get_or_init(|| {
+            RTree::bulk_load(
+                self.edges
+                    .iter()
+                    .map(|e| GeomWithData::new(e.linestring.clone(), e.id))
+                    .collect(),
+            )
+        })
+    }
+}
+
+/// Serialize `edge_to_relations` as a list of `(EdgeID, relations)` pairs, sorted by edge. Formats
+/// like JSON only allow string map keys, so this avoids depending on how they encode `EdgeID`.
+#[cfg(feature = "serde")]
+mod edge_map_as_pairs {
+    use std::collections::HashMap;
+
+    use osm_reader::RelationID;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::EdgeID;
+
+    pub fn serialize<S: Serializer>(
+        map: &HashMap<EdgeID, Vec<RelationID>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut pairs: Vec<(&EdgeID, &Vec<RelationID>)> = map.iter().collect();
+        pairs.sort_by_key(|(e, _)| **e);
+        pairs.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<EdgeID, Vec<RelationID>>, D::Error> {
+        let pairs: Vec<(EdgeID, Vec<RelationID>)> = Vec::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+/// Find the root in a union-find structure, compressing the path along the way
+fn find(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+/// Everything collected while reading OSM elements, before building the graph
+#[derive(Default)]
+struct Scraper {
+    node_mapping: HashMap<NodeID, Coord>,
+    node_tags: HashMap<NodeID, Tags>,
+    highways: Vec<Way>,
+    barriers: Vec<(WayID, LineString, Tags)>,
+    relations: Vec<Relation>,
+}
+
+impl Scraper {
+    fn handle<R: OsmReader>(
+        &mut self,
+        elem: Element,
+        keep_edge: &impl Fn(&Tags) -> bool,
+        keep_barrier: &impl Fn(&Tags) -> bool,
+        reader: &mut R,
+    ) {
+        match elem {
+            Element::Node {
+                id, lon, lat, tags, ..
+            } => {
+                let pt = Coord { x: lon, y: lat };
+                self.node_mapping.insert(id, pt);
+                let tags: Tags = tags.into();
+                if !tags.0.is_empty() {
+                    self.node_tags.insert(id, tags.clone());
+                }
+                reader.node(id, pt, tags);
+            }
+            Element::Way {
+                id,
+                mut node_ids,
+                tags,
+                ..
+            } => {
+                let tags: Tags = tags.into();
+
+                // TODO This sometimes happens from Overpass?
+                let runs = split_at_missing_nodes(&node_ids, &self.node_mapping);
+                let num = node_ids.len();
+                node_ids.retain(|n| self.node_mapping.contains_key(n));
+                if node_ids.len() != num {
+                    warn!("{id} refers to nodes outside the imported area");
+                }
+
+                reader.way(id, &node_ids, &self.node_mapping, &tags);
+
+                // Don't connect nodes on either side of a gap. Each run of consecutive nodes
+                // becomes a separate piece of the way.
+                for node_ids in runs {
+                    if keep_edge(&tags) {
+                        self.highways.push(Way {
+                            id,
+                            node_ids,
+                            tags: tags.clone(),
+                        });
+                    } else if keep_barrier(&tags) {
+                        let linestring: LineString =
+                            node_ids.iter().map(|n| self.node_mapping[n]).collect();
+                        self.barriers.push((id, linestring, tags.clone()));
+                    }
+                }
+            }
+            Element::Relation {
+                id, tags, members, ..
+            } => {
+                let tags: Tags = tags.into();
+                let members: Vec<(OsmID, String)> = members
+                    .into_iter()
+                    .map(|(role, member)| (member, role))
+                    .collect();
+                reader.relation(id, &members, &tags);
+                self.relations.push(Relation { id, members, tags });
+            }
+            Element::Bounds { .. } => {}
+        }
+    }
+
+    fn finish(self, clip: Option<Polygon>) -> Graph {
+        let Scraper {
+            mut node_mapping,
+            node_tags,
+            highways,
+            mut barriers,
+            relations,
+        } = self;
+        let mut graph = if let Some(clip) = clip {
+            let highways = clip_ways(&mut node_mapping, highways, &clip);
+            Graph::from_scraped_osm_with_boundary(node_mapping, node_tags, highways, clip)
+        } else {
+            Graph::from_scraped_osm(node_mapping, node_tags, highways, |_| true)
+        };
+        for (_, linestring, _) in &mut barriers {
+            graph.mercator.to_mercator_in_place(linestring);
+        }
+        graph.barriers = barriers;
+        graph.index_relations(&relations);
+        graph
+    }
+}
+
+/// Split a way's nodes into runs of consecutive nodes that exist in `node_mapping`. Runs with
+/// fewer than 2 nodes are dropped.
+fn split_at_missing_nodes(
+    node_ids: &[NodeID],
+    node_mapping: &HashMap<NodeID, Coord>,
+) -> Vec<Vec<NodeID>> {
+    node_ids
+        .split(|n| !node_mapping.contains_key(n))
+        .filter(|run| run.len() >= 2)
+        .map(|run| run.to_vec())
+        .collect()
+}
+
+fn first_crossing(ls1: &LineString, ls2: &LineString) -> Option<Point> {
+    for line1 in ls1.lines() {
+        for line2 in ls2.lines() {
+            match line_intersection(line1, line2) {
+                Some(LineIntersection::SinglePoint { intersection, .. }) => {
+                    return Some(intersection.into());
+                }
+                Some(LineIntersection::Collinear { intersection }) => {
+                    return Some(intersection.start.into());
+                }
+                None => {}
+            }
+        }
+    }
+    None
+}
+
+/// An edge in an RTree, approximated by its bounding box
+#[derive(Clone, Debug)]
+pub struct EdgeRef {
+    pub id: EdgeID,
+    pub aabb: AABB<Point>,
+}
+
+impl RTreeObject for EdgeRef {
+    type Envelope = AABB<Point>;
+
+    fn envelope(&self) -> Self::Envelope {
+        self.aabb
+    }
+}
+
+impl PointDistance for EdgeRef {
+    fn distance_2(&self, point: &Point) -> f64 {
+        self.aabb.distance_2(point)
+    }
+}
+
+/// Resolve `type=restriction` relations into edges and intersections. Restrictions with a `via`
+/// way, with members outside the imported area, or where the `from` or `to` way continues through
+/// the `via` node (so it's unclear which side is meant) are skipped with a warning.
+pub fn parse_turn_restrictions(graph: &Graph, relations: &[Relation]) -> Vec<TurnRestriction> {
+    let node_to_intersection: HashMap<NodeID, IntersectionID> = graph
+        .intersections
+        .iter()
+        .flat_map(|i| {
+            std::iter::once(&i.osm_node)
+                .chain(&i.merged_osm_nodes)
+                .map(move |n| (*n, i.id))
+        })
+        .collect();
+    // Find the edges belonging to a way and touching an intersection
+    let find_edges = |way: WayID, via: IntersectionID| {
+        let mut edges: Vec<EdgeID> = graph.intersections[via.0]
+            .edges
+            .iter()
+            .filter(|e| graph.edges[e.0].osm_way == way)
+            .cloned()
+            .collect();
+        // Self-loops are listed twice
+        edges.sort();
+        edges.dedup();
+        edges
+    };
+
+    let mut results = Vec::new();
+    'relations: for relation in relations {
+        if !relation.tags.is("type", "restriction") {
+            continue;
+        }
+        let Some(restriction) = relation.tags.get("restriction") else {
+            warn!("Skipping {}, it has no restriction tag", relation.id);
+            continue;
+        };
+        let only = if restriction.starts_with("only_") {
+            true
+        } else if restriction.starts_with("no_") {
+            false
+        } else {
+            warn!(
+                "Skipping {}, unknown restriction={restriction}",
+                relation.id
+            );
+            continue;
+        };
+
+        let mut from = None;
+        let mut via = None;
+        let mut to = None;
+        for (member, role) in &relation.members {
+            match (role.as_str(), member) {
+                ("from", OsmID::Way(w)) => from = Some(*w),
+                ("to", OsmID::Way(w)) => to = Some(*w),
+                ("via", OsmID::Node(n)) => via = Some(*n),
+                ("via", OsmID::Way(_)) => {
+                    warn!("Skipping {}, via ways aren't supported", relation.id);
+                    continue 'relations;
+                }
+                _ => {}
+            }
+        }
+        let (Some(from), Some(via), Some(to)) = (from, via, to) else {
+            warn!("Skipping {}, it's missing a from, via, or to", relation.id);
+            continue;
+        };
+
+        let Some(via) = node_to_intersection.get(&via).cloned() else {
+            warn!("Skipping {}, the via node isn't in the graph", relation.id);
+            continue;
+        };
+        let from_edges = find_edges(from, via);
+        let to_edges = find_edges(to, via);
+        let (from, to) = match (from_edges.as_slice(), to_edges.as_slice()) {
+            ([from], [to]) => (*from, *to),
+            ([], _) | (_, []) => {
+                warn!("Skipping {}, members aren't in the graph", relation.id);
+                continue;
+            }
+            _ => {
+                warn!(
+                    "Skipping {}, the from or to way continues through the via node",
+                    relation.id
+                );
+                continue;
+            }
+        };
+        results.push(TurnRestriction {
+            osm_relation: relation.id,
+            from,
+            via,
+            to,
+            only,
+            restriction: restriction.clone(),
+        });
+    }
+    results
+}
+
+/// Truncate ways at the boundary of `clip`, keeping only the pieces inside. Where a way crosses
+/// the boundary, a new node with a negative ID is created.
+fn clip_ways(
+    node_mapping: &mut HashMap<NodeID, Coord>,
+    ways: Vec<Way>,
+    clip: &Polygon,
+) -> Vec<Way> {
+    let rings: Vec<Line> = std::iter::once(clip.exterior())
+        .chain(clip.interiors())
+        .flat_map(|ring| ring.lines())
+        .collect();
+    let mut next_id = -1;
+
+    let mut output = Vec::new();
+    for way in ways {
+        // Expand the way with crossing points
+        let mut nodes = vec![way.node_ids[0]];
+        for pair in way.node_ids.windows(2) {
+            let a = node_mapping[&pair[0]];
+            let b = node_mapping[&pair[1]];
+            let segment = Line::new(a, b);
+            let mut crossings: Vec<(f64, Coord)> = rings
+                .iter()
+                .filter_map(|ring| match line_intersection(segment, *ring) {
+                    Some(LineIntersection::SinglePoint { intersection, .. }) => {
+                        let (v, w) = (intersection - a, b - a);
+                        let t = (v.x * w.x + v.y * w.y) / (w.x * w.x + w.y * w.y);
+                        (t > 0.0 && t < 1.0).then_some((t, intersection))
+                    }
+                    _ => None,
+                })
+                .collect();
+            crossings.sort_by(|x, y| x.0.total_cmp(&y.0));
+            // Crossing exactly at a vertex of the ring hits both adjacent ring segments
+            crossings.dedup_by(|x, y| (x.0 - y.0).abs() < 1e-9);
+            for (_, pt) in crossings {
+                let id = NodeID(next_id);
+                next_id -= 1;
+                node_mapping.insert(id, pt);
+                nodes.push(id);
+            }
+            nodes.push(pair[1]);
+        }
+
+        // Keep runs of pieces that're inside
+        let mut current: Vec<NodeID> = Vec::new();
+        for pair in nodes.windows(2) {
+            let midpoint = (node_mapping[&pair[0]] + node_mapping[&pair[1]]) / 2.0;
+            if clip.intersects(&Point(midpoint)) {
+                if current.is_empty() {
+                    current.push(pair[0]);
+                }
+                current.push(pair[1]);
+            } else if !current.is_empty() {
+                output.push(Way {
+                    id: way.id,
+                    node_ids: std::mem::take(&mut current),
+                    tags: way.tags.clone(),
+                });
+            }
+        }
+        if !current.is_empty() {
+            output.push(Way {
+                id: way.id,
+                node_ids: current,
+                tags: way.tags,
+            });
+        }
+    }
+    output
+}
+
+fn split_edges(
+    node_mapping: HashMap<NodeID, Coord>,
+    node_tags: HashMap<NodeID, Tags>,
+    ways: Vec<Way>,
+) -> (Vec<Edge>, Vec<Intersection>) {
+    // Count how many times each node is referenced. A node repeated within one way counts more
+    // than once, so self-intersecting ways are split there too.
+    let mut node_counter: HashMap<NodeID, usize> = HashMap::new();
+    for way in &ways {
+        for node in &way.node_ids {
+            *node_counter.entry(*node).or_insert(0) += 1;
+        }
+    }
+
+    // Split each way into edges
+    let mut node_to_intersection: HashMap<NodeID, IntersectionID> = HashMap::new();
+    let mut intersections = Vec::new();
+    let mut edges = Vec::new();
+    for way in ways {
+        let mut node1 = way.node_ids[0];
+        let mut pts = Vec::new();
+        let mut nodes = Vec::new();
+
+        let num_nodes = way.node_ids.len();
+        for (idx, node) in way.node_ids.into_iter().enumerate() {
+            pts.push(node_mapping[&node]);
+            nodes.push(node);
+            // Edges start/end at intersections between two ways. The endpoints of the way also
+            // count as intersections.
+            let is_endpoint =
+                idx == 0 || idx == num_nodes - 1 || *node_counter.get(&node).unwrap() > 1;
+            if is_endpoint && pts.len() > 1 {
+                let edge_id = EdgeID(edges.len());
+
+                let mut i_ids = Vec::new();
+                for (n, point) in [(node1, pts[0]), (node, *pts.last().unwrap())] {
+                    let intersection = if let Some(i) = node_to_intersection.get(&n) {
+                        &mut intersections[i.0]
+                    } else {
+                        let i = IntersectionID(intersections.len());
+                        intersections.push(Intersection {
+                            id: i,
+                            osm_node: n,
+                            osm_tags: node_tags.get(&n).cloned().unwrap_or_else(Tags::empty),
+                            merged_osm_nodes: Vec::new(),
+                            point: Point(point),
+                            edges: Vec::new(),
+                        });
+                        node_to_intersection.insert(n, i);
+                        &mut intersections[i.0]
+                    };
+
+                    intersection.edges.push(edge_id);
+                    i_ids.push(intersection.id);
+                }
+
+                edges.push(Edge {
+                    id: edge_id,
+                    src: i_ids[0],
+                    dst: i_ids[1],
+                    osm_way: way.id,
+                    osm_node1: node1,
+                    osm_node2: node,
+                    osm_nodes: std::mem::take(&mut nodes),
+                    osm_tags: way.tags.clone(),
+                    direction: Direction::from_osm(&way.tags),
+                    linestring: LineString::new(std::mem::take(&mut pts)),
+                    // Calculated after projecting
+                    length_meters: 0.0,
+                    merged_osm_ways: Vec::new(),
+                });
+
+                // Start the next edge
+                node1 = node;
+                pts.push(node_mapping[&node]);
+                nodes.push(node);
+            }
+        }
+    }
+
+    (edges, intersections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Node IDs with their (longitude, latitude)
+    fn node_mapping(nodes: &[(i64, f64, f64)]) -> HashMap<NodeID, Coord> {
+        nodes
+            .iter()
+            .map(|(n, x, y)| (NodeID(*n), Coord { x: *x, y: *y }))
+            .collect()
+    }
+
+    /// A way without tags
+    fn way(id: i64, nodes: &[i64]) -> Way {
+        Way {
+            id: WayID(id),
+            node_ids: nodes.iter().map(|n| NodeID(*n)).collect(),
+            tags: Tags::empty(),
+        }
+    }
+
+    fn test_graph(nodes: &[(i64, f64, f64)], ways: Vec<Way>) -> Graph {
+        Graph::from_scraped_osm(node_mapping(nodes), HashMap::new(), ways, |_| true)
+    }
+
+    #[test]
+    fn split_self_intersecting_way() {
+        let node_mapping = node_mapping(&[
+            (1, 0.0, 0.0),
+            (2, 0.0, 0.001),
+            (3, 0.001, 0.002),
+            (4, 0.0, 0.003),
+        ]);
+        let ways = vec![way(10, &[1, 2, 3, 2, 4])];
+        let (edges, intersections) = split_edges(node_mapping, HashMap::new(), ways);
+
+        let endpoints: Vec<(NodeID, NodeID)> =
+            edges.iter().map(|e| (e.osm_node1, e.osm_node2)).collect();
+        assert_eq!(
+            endpoints,
+            vec![
+                (NodeID(1), NodeID(2)),
+                (NodeID(2), NodeID(2)),
+                (NodeID(2), NodeID(4))
+            ]
+        );
+        assert_eq!(intersections.len(), 3);
+    }
+
+    #[test]
+    fn direction() {
+        for (tags, expected) in [
+            (vec![], Direction::Both),
+            (vec![("oneway", "yes")], Direction::Forward),
+            (vec![("oneway", "1")], Direction::Forward),
+            (vec![("oneway", "-1")], Direction::Backward),
+            (vec![("oneway", "no")], Direction::Both),
+            (vec![("oneway", "reversible")], Direction::Both),
+            (vec![("junction", "roundabout")], Direction::Forward),
+            (
+                vec![("junction", "roundabout"), ("oneway", "no")],
+                Direction::Both,
+            ),
+            (
+                vec![("junction", "roundabout"), ("oneway", "-1")],
+                Direction::Backward,
+            ),
+            (vec![("highway", "motorway")], Direction::Forward),
+            (
+                vec![("highway", "motorway"), ("oneway", "no")],
+                Direction::Both,
+            ),
+        ] {
+            let tags: Tags = tags
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            assert_eq!(Direction::from_osm(&tags), expected, "{tags:?}");
+        }
+
+        let mut graph = test_graph(&[(1, 0.0, 0.0), (2, 0.001, 0.0)], vec![way(10, &[1, 2])]);
+        let (src, dst) = (graph.edges[0].src, graph.edges[0].dst);
+        for (direction, from_src, from_dst) in [
+            (Direction::Forward, true, false),
+            (Direction::Backward, false, true),
+            (Direction::Both, true, true),
+        ] {
+            graph.edges[0].direction = direction;
+            assert_eq!(graph.edges[0].allows(src), from_src);
+            assert_eq!(graph.edges[0].allows(dst), from_dst);
+        }
+    }
+
+    #[test]
+    fn osm_stable_ids() {
+        let nodes = [
+            (1, 0.0, 0.0),
+            (2, 0.001, 0.0),
+            (3, 0.002, 0.0),
+            (4, 0.0, 0.001),
+        ];
+        let mut before = test_graph(&nodes, vec![way(10, &[1, 2]), way(11, &[2, 3])]);
+        before.assign_osm_stable_ids();
+        // Same data, listed in a different order
+        let mut again = test_graph(&nodes, vec![way(11, &[2, 3]), way(10, &[1, 2])]);
+        again.assign_osm_stable_ids();
+        assert_eq!(before.edges, again.edges);
+        assert_eq!(before.intersections, again.intersections);
+
+        // A new way with a smaller ID shifts every EdgeID, but osm_key still matches
+        let mut after = test_graph(
+            &nodes,
+            vec![way(5, &[1, 4]), way(10, &[1, 2]), way(11, &[2, 3])],
+        );
+        after.assign_osm_stable_ids();
+        after.validate().unwrap();
+        for e in &before.edges {
+            let matching = after
+                .edges
+                .iter()
+                .find(|x| x.osm_key() == e.osm_key())
+                .unwrap();
+            assert_eq!(matching.id, EdgeID(e.id.0 + 1));
+        }
+        // The new node sorts last, so existing intersections keep their IDs
+        for i in &before.intersections {
+            assert_eq!(after.intersections[i.id.0].osm_node, i.osm_node);
+        }
+    }
+
+    #[test]
+    fn components() {
+        let mut graph = test_graph(
+            &[
+                (1, 0.0, 0.0),
+                (2, 0.001, 0.0),
+                (3, 0.002, 0.0),
+                (4, 0.0, 0.001),
+                (5, 0.001, 0.001),
+            ],
+            vec![way(10, &[1, 2]), way(11, &[2, 3]), way(12, &[4, 5])],
+        );
+        let osm_nodes = |graph: &Graph, component: &BTreeSet<IntersectionID>| {
+            let mut nodes: Vec<i64> = component
+                .iter()
+                .map(|i| graph.intersections[i.0].osm_node.0)
+                .collect();
+            nodes.sort();
+            nodes
+        };
+        let components = graph.connected_components();
+        assert_eq!(components.len(), 2);
+        assert_eq!(osm_nodes(&graph, &components[0]), vec![1, 2, 3]);
+        assert_eq!(osm_nodes(&graph, &components[1]), vec![4, 5]);
+
+        graph.retain_largest_component();
+        graph.validate().unwrap();
+        let mut ways: Vec<i64> = graph.edges.iter().map(|e| e.osm_way.0).collect();
+        ways.sort();
+        assert_eq!(ways, vec![10, 11]);
+        assert_eq!(graph.intersections.len(), 3);
+        assert_eq!(graph.connected_components().len(), 1);
+    }
+
+    #[test]
+    fn project_once() {
+        let mut graph = Graph::from_scraped_osm_wgs84(
+            node_mapping(&[(1, -0.1, 51.5), (2, -0.2, 51.6)]),
+            HashMap::new(),
+            vec![way(10, &[1, 2])],
+            Rect::new(Coord { x: -0.3, y: 51.4 }, Coord { x: 0.0, y: 51.7 }).to_polygon(),
+        );
+        assert_eq!(graph.intersections[0].point, Point::new(-0.1, 51.5));
+        graph.project();
+        let projected = graph.edges[0].linestring.clone();
+        assert_ne!(graph.intersections[0].point, Point::new(-0.1, 51.5));
+
+        graph.project();
+        assert_eq!(graph.edges[0].linestring, projected);
+    }
+
+    #[test]
+    fn crossing_without_node() {
+        // Two ways form an X, but don't share a node
+        let graph = test_graph(
+            &[
+                (1, 0.0, 0.0),
+                (2, 0.002, 0.002),
+                (3, 0.0, 0.002),
+                (4, 0.002, 0.0),
+            ],
+            vec![way(10, &[1, 2]), way(11, &[3, 4])],
+        );
+
+        let crossings = graph.find_geometric_crossings_without_nodes();
+        assert_eq!(crossings.len(), 1);
+        assert_eq!((crossings[0].0, crossings[0].1), (EdgeID(0), EdgeID(1)));
+    }
+
+    #[test]
+    fn way_with_missing_node() {
+        // Node 3 is referenced, but missing
+        let input = r#"<osm version="0.6">
+            <node id="1" lat="0.0" lon="0.0" version="1"/>
+            <node id="2" lat="0.0" lon="0.001" version="1"/>
+            <node id="4" lat="0.0" lon="0.003" version="1"/>
+            <node id="5" lat="0.0" lon="0.004" version="1"/>
+            <way id="10" version="1">
+                <nd ref="1"/>
+                <nd ref="2"/>
+                <nd ref="3"/>
+                <nd ref="4"/>
+                <nd ref="5"/>
+                <tag k="highway" v="residential"/>
+            </way>
+        </osm>"#;
+        let graph = Graph::new(
+            input.as_bytes(),
+            |tags| tags.has("highway"),
+            |_| false,
+            &mut NullReader,
+            None,
+        )
+        .unwrap();
+
+        let mut endpoints: Vec<(i64, i64)> = graph
+            .edges
+            .iter()
+            .map(|e| (e.osm_node1.0, e.osm_node2.0))
+            .collect();
+        endpoints.sort();
+        assert_eq!(endpoints, vec![(1, 2), (4, 5)]);
+    }
+
+    #[test]
+    fn intersection_polygons() {
+        let graph = test_graph(
+            &[(1, 0.0, 0.0), (2, 0.001, 0.0), (3, 0.0, 0.001)],
+            vec![way(10, &[1, 2]), way(11, &[2, 3])],
+        );
+
+        for i in &graph.intersections {
+            let polygon = graph.intersection_polygon(i.id, 5.0).unwrap();
+            assert!(polygon.unsigned_area() > 0.0);
+            // Every point is within the radius, plus half the width
+            for pt in polygon.exterior().points() {
+                let dist = (pt.x() - i.point.x()).hypot(pt.y() - i.point.y());
+                assert!(dist <= 5.0_f64.hypot(2.5) + 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn densify() {
+        let mut graph = test_graph(
+            &[(1, 0.0, 0.0), (2, 0.001, 0.0), (3, 0.001, 0.001)],
+            vec![way(10, &[1, 2, 3])],
+        );
+        let original = graph.edges[0].linestring.clone();
+
+        graph.densify_edges(10.0);
+        let densified = &graph.edges[0].linestring;
+        assert!(densified
+            .lines()
+            .all(|l| l.euclidean_length() <= 10.0 + 1e-9));
+        assert!(original.0.iter().all(|pt| densified.0.contains(pt)));
+        assert_eq!(graph.edges[0].osm_nodes.len(), 3);
+    }
+
+    #[test]
+    fn cluster() {
+        // Intersections 1 and 2 are about 3m apart
+        let mut graph = test_graph(
+            &[
+                (1, 0.0, 0.0),
+                (2, 0.00003, 0.0),
+                (3, 0.001, 0.0),
+                (4, 0.0, 0.001),
+            ],
+            vec![way(10, &[1, 2]), way(11, &[2, 3]), way(12, &[1, 4])],
+        );
+
+        graph.cluster_intersections(5.0);
+        graph.validate().unwrap();
+        assert_eq!(graph.intersections.len(), 3);
+        assert_eq!(graph.edges.len(), 2);
+        let merged = graph
+            .intersections
+            .iter()
+            .find(|i| i.edges.len() == 2)
+            .unwrap();
+        for e in &graph.edges {
+            assert!(e.src == merged.id || e.dst == merged.id);
+            assert!(e.linestring.0.contains(&merged.point.0));
+        }
+        assert_eq!(merged.osm_node, NodeID(1));
+        assert_eq!(merged.merged_osm_nodes, vec![NodeID(2)]);
+
+        // Edges keep their real OSM nodes
+        let edge = graph.edges.iter().find(|e| e.osm_way == WayID(11)).unwrap();
+        assert_eq!(edge.osm_nodes, vec![NodeID(2), NodeID(3)]);
+        assert_eq!((edge.osm_node1, edge.osm_node2), (NodeID(2), NodeID(3)));
+        assert_eq!(graph.edges_at_osm_node(NodeID(2)).len(), 2);
+    }
+
+    #[test]
+    fn edge_filter() {
+        let ways = vec![way(10, &[1, 2]), way(11, &[2, 3]), way(12, &[1, 4])];
+        let graph = Graph::from_scraped_osm(
+            node_mapping(&[
+                (1, 0.0, 0.0),
+                (2, 0.001, 0.0),
+                (3, 0.002, 0.0),
+                (4, 0.0, 0.001),
+            ]),
+            HashMap::new(),
+            ways,
+            |e| {
+                e.osm_tags.insert("checked", "yes");
+                e.osm_way != WayID(11)
+            },
+        );
+
+        graph.validate().unwrap();
+        assert_eq!(graph.edges.len(), 2);
+        assert!(graph.edges.iter().all(|e| e.osm_tags.is("checked", "yes")));
+        // The intersection at node 3 is orphaned and removed
+        assert_eq!(graph.intersections.len(), 3);
+    }
+
+    #[test]
+    fn remove_and_return() {
+        let mut graph = test_graph(
+            &[(1, 0.0, 0.0), (2, 0.001, 0.0), (3, 0.002, 0.0)],
+            vec![way(10, &[1, 2]), way(11, &[2, 3])],
+        );
+        let removed_id = graph
+            .edges
+            .iter()
+            .find(|e| e.osm_way == WayID(11))
+            .unwrap()
+            .id;
+
+        let (edges, intersections) = graph.remove_edges_returning(BTreeSet::from([removed_id]));
+        graph.validate().unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].id, removed_id);
+        assert_eq!(intersections.len(), 1);
+        assert_eq!(intersections[0].osm_node, NodeID(3));
+        assert_eq!(intersections[0].edges, vec![removed_id]);
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(graph.intersections.len(), 2);
+    }
+
+    #[test]
+    fn buckets() {
+        let graph = test_graph(
+            &[(1, 0.0, 0.0), (2, 0.001, 0.0), (3, 0.001, 0.001)],
+            vec![way(10, &[1, 2]), way(11, &[2, 3])],
+        );
+
+        let cell_meters = 20.0;
+        let buckets = graph.bucket_edges(cell_meters);
+        for e in &graph.edges {
+            for pt in &e.linestring {
+                let key = (
+                    (pt.x / cell_meters).floor() as i32,
+                    (pt.y / cell_meters).floor() as i32,
+                );
+                assert!(buckets[&key].contains(&e.id));
+            }
+        }
+        // The two edges only meet at one corner
+        let shared = buckets.values().filter(|ids| ids.len() == 2).count();
+        assert!((1..=4).contains(&shared));
+    }
+
+    #[test]
+    #[should_panic]
+    fn buckets_need_finite_size() {
+        let graph = test_graph(&[(1, 0.0, 0.0), (2, 0.001, 0.0)], vec![way(10, &[1, 2])]);
+        graph.bucket_edges(f64::INFINITY);
+    }
+
+    #[test]
+    fn bounds() {
+        let graph = test_graph(&[(1, -0.1, 51.5), (2, -0.2, 51.6)], vec![way(10, &[1, 2])]);
+
+        let mercator = graph.mercator_bounds();
+        assert_eq!(mercator.min(), Coord { x: 0.0, y: 0.0 });
+        assert!(mercator.width() > 0.0 && mercator.height() > 0.0);
+        let wgs84 = graph.wgs84_bounds();
+        for (actual, expected) in [
+            (wgs84.min(), Coord { x: -0.2, y: 51.5 }),
+            (wgs84.max(), Coord { x: -0.1, y: 51.6 }),
+        ] {
+            assert!((actual.x - expected.x).abs() < 1e-9);
+            assert!((actual.y - expected.y).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn invalidate_index() {
+        let mut graph = test_graph(
+            &[(1, 0.0, 0.0), (2, 0.001, 0.0), (3, 0.002, 0.0)],
+            vec![way(10, &[1, 2]), way(11, &[2, 3])],
+        );
+        let end = graph.edges.iter().find(|e| e.osm_way == WayID(11)).unwrap();
+        let pt = end.linestring.0[1].into();
+        assert_eq!(graph.snap_to_edge(pt).unwrap().0, end.id);
+
+        // Keep only the other edge, without going through remove_edges
+        graph.edges.retain(|e| e.osm_way == WayID(10));
+        graph.edges[0].id = EdgeID(0);
+        graph.invalidate_edge_index();
+        assert_eq!(graph.snap_to_edge(pt).unwrap().0, EdgeID(0));
+    }
+
+    #[test]
+    fn turn_restrictions() {
+        // Way 10 leads east to node 2, then way 11 continues east and way 12 goes north. Way 13
+        // passes through node 2 from south-west to south-east.
+        let graph = test_graph(
+            &[
+                (1, 0.0, 0.0),
+                (2, 0.001, 0.0),
+                (3, 0.002, 0.0),
+                (4, 0.001, 0.001),
+                (5, 0.0005, -0.001),
+                (6, 0.0015, -0.001),
+            ],
+            vec![
+                way(10, &[1, 2]),
+                way(11, &[2, 3]),
+                way(12, &[2, 4]),
+                way(13, &[5, 2, 6]),
+            ],
+        );
+        let restriction = |id: i64, value: &str, members: Vec<(OsmID, &str)>| Relation {
+            id: RelationID(id),
+            members: members
+                .into_iter()
+                .map(|(member, role)| (member, role.to_string()))
+                .collect(),
+            tags: Tags::from([("type", "restriction"), ("restriction", value)]),
+        };
+        let (from, via, to) = ("from", "via", "to");
+        let relations = vec![
+            restriction(
+                100,
+                "no_left_turn",
+                vec![
+                    (OsmID::Way(WayID(10)), from),
+                    (OsmID::Node(NodeID(2)), via),
+                    (OsmID::Way(WayID(12)), to),
+                ],
+            ),
+            restriction(
+                101,
+                "only_straight_on",
+                vec![
+                    (OsmID::Way(WayID(10)), from),
+                    (OsmID::Node(NodeID(2)), via),
+                    (OsmID::Way(WayID(11)), to),
+                ],
+            ),
+            // Via ways aren't supported
+            restriction(
+                102,
+                "no_u_turn",
+                vec![
+                    (OsmID::Way(WayID(10)), from),
+                    (OsmID::Way(WayID(11)), via),
+                    (OsmID::Way(WayID(12)), to),
+                ],
+            ),
+            // Way 13 has an edge on both sides of node 2
+            restriction(
+                103,
+                "no_right_turn",
+                vec![
+                    (OsmID::Way(WayID(13)), from),
+                    (OsmID::Node(NodeID(2)), via),
+                    (OsmID::Way(WayID(12)), to),
+                ],
+            ),
+            // No to
+            restriction(
+                104,
+                "no_left_turn",
+                vec![(OsmID::Way(WayID(10)), from), (OsmID::Node(NodeID(2)), via)],
+            ),
+        ];
+
+        let edge = |w: i64| {
+            graph
+                .edges
+                .iter()
+                .find(|e| e.osm_way == WayID(w))
+                .unwrap()
+                .id
+        };
+        let via = graph
+            .intersections
+            .iter()
+            .find(|i| i.osm_node == NodeID(2))
+            .unwrap()
+            .id;
+        assert_eq!(
+            parse_turn_restrictions(&graph, &relations),
+            vec![
+                TurnRestriction {
+                    osm_relation: RelationID(100),
+                    from: edge(10),
+                    via,
+                    to: edge(12),
+                    only: false,
+                    restriction: "no_left_turn".to_string(),
+                },
+                TurnRestriction {
+                    osm_relation: RelationID(101),
+                    from: edge(10),
+                    via,
+                    to: edge(11),
+                    only: true,
+                    restriction: "only_straight_on".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn clip() {
+        let clip = Rect::new(Coord { x: 0.0, y: 0.0 }, Coord { x: 0.001, y: 0.001 }).to_polygon();
+        let mut node_mapping = node_mapping(&[
+            (1, 0.0002, 0.0002),
+            (2, 0.0008, 0.0002),
+            (3, 0.002, 0.002),
+            (4, 0.003, 0.002),
+            (5, 0.0005, 0.0005),
+            (6, 0.0015, 0.0005),
+            (7, 0.0005, 0.0005),
+            (8, 0.0015, 0.0015),
+        ]);
+        let ways = vec![
+            // Inside
+            way(10, &[1, 2]),
+            // Outside
+            way(11, &[3, 4]),
+            // Crossing the east side
+            way(12, &[5, 6]),
+            // Crossing the north-east corner
+            way(13, &[7, 8]),
+        ];
+        let ways = clip_ways(&mut node_mapping, ways, &clip);
+
+        let node_ids: Vec<(WayID, Vec<i64>)> = ways
+            .iter()
+            .map(|w| (w.id, w.node_ids.iter().map(|n| n.0).collect()))
+            .collect();
+        assert_eq!(
+            node_ids,
+            vec![
+                (WayID(10), vec![1, 2]),
+                (WayID(12), vec![5, -1]),
+                (WayID(13), vec![7, -2]),
+            ]
+        );
+        for (node, pt) in [(-1, (0.001, 0.0005)), (-2, (0.001, 0.001))] {
+            let actual = node_mapping[&NodeID(node)];
+            assert!((actual.x - pt.0).abs() < 1e-12 && (actual.y - pt.1).abs() < 1e-12);
+        }
+
+        let graph = Graph::from_scraped_osm_with_boundary(node_mapping, HashMap::new(), ways, clip);
+        graph.validate().unwrap();
+        let boundary = graph
+            .intersections
+            .iter()
+            .find(|i| i.osm_node == NodeID(-1))
+            .unwrap();
+        assert!(graph.boundary_edges().contains(&boundary.edges[0]));
+    }
+
+    #[test]
+    fn prune_dead_ends() {
+        // A road running east-west through node 1, with a short stub to the north, and a long
+        // stub to the south made of ten 20m ways
+        let mut nodes = vec![
+            (1, 0.0, 0.0),
+            (2, 0.002, 0.0),
+            (3, -0.002, 0.0),
+            (4, 0.0, 0.0001),
+        ];
+        let mut ways = vec![way(10, &[3, 1]), way(11, &[1, 2]), way(12, &[1, 4])];
+        for k in 0..10 {
+            nodes.push((5 + k, 0.0, -0.00018 * (k + 1) as f64));
+            let prev = if k == 0 { 1 } else { 4 + k };
+            ways.push(way(20 + k, &[prev, 5 + k]));
+        }
+        let mut graph = test_graph(&nodes, ways);
+
+        let junction = graph
+            .intersections
+            .iter()
+            .find(|i| i.osm_node == NodeID(1))
+            .unwrap()
+            .id;
+        assert_eq!(graph.degree(junction), 4);
+        let mut dead_ends: Vec<i64> = graph
+            .dead_ends()
+            .into_iter()
+            .map(|i| graph.intersections[i.0].osm_node.0)
+            .collect();
+        dead_ends.sort();
+        assert_eq!(dead_ends, vec![2, 3, 4, 14]);
+
+        graph.prune_dead_end_paths(30.0);
+        graph.validate().unwrap();
+        assert_eq!(graph.edges.len(), 12);
+        assert!(graph.edges.iter().all(|e| e.osm_way != WayID(12)));
+    }
+
+    #[test]
+    fn triangle_neighbors() {
+        let graph = test_graph(
+            &[(1, 0.0, 0.0), (2, 0.001, 0.0), (3, 0.0, 0.001)],
+            vec![way(10, &[1, 2]), way(11, &[2, 3]), way(12, &[3, 1])],
+        );
+
+        for i in &graph.intersections {
+            let mut neighbors: Vec<NodeID> = graph
+                .neighbors(i.id)
+                .into_iter()
+                .map(|(_, other)| graph.intersections[other.0].osm_node)
+                .collect();
+            neighbors.sort_by_key(|n| n.0);
+            let expected: Vec<NodeID> = [1, 2, 3]
+                .into_iter()
+                .map(NodeID)
+                .filter(|n| *n != i.osm_node)
+                .collect();
+            assert_eq!(neighbors, expected);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut graph = test_graph(
+            &[(1, -0.1, 51.5), (2, -0.1, 51.6), (3, -0.2, 51.6)],
+            vec![Way {
+                tags: Tags::from([("highway", "residential")]),
+                ..way(10, &[1, 2, 3])
+            }],
+        );
+        // Cover fields that're normally empty in a small import
+        graph.edges[0].merged_osm_ways.push(WayID(11));
+        graph
+            .edge_to_relations
+            .insert(EdgeID(0), vec![RelationID(20)]);
+        graph.barriers.push((
+            WayID(30),
+            graph.edges[0].linestring.clone(),
+            Tags::from([("barrier", "wall")]),
+        ));
+
+        let json = serde_json::to_string(&graph).unwrap();
+        // Keyed by EdgeID, so stored as a list of pairs
+        assert!(json.contains(r#""edge_to_relations":[[0,[20]]]"#));
+        let copy: Graph = serde_json::from_str(&json).unwrap();
+        assert_eq!(graph.edges, copy.edges);
+        assert_eq!(graph.intersections, copy.intersections);
+        assert_eq!(graph.edge_to_relations, copy.edge_to_relations);
+        assert_eq!(graph.barriers, copy.barriers);
+        assert_eq!(graph.boundary_polygon, copy.boundary_polygon);
+        assert_eq!(graph.mercator.wgs84_bounds, copy.mercator.wgs84_bounds);
+    }
+}
@@ -1,9 +1,15 @@
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::ops::Add;
 
 /// Use with `BinaryHeap`. Since it's a max-heap, reverse the comparison to get the smallest cost
 /// first.
+///
+/// Items with equal cost are ordered by value, so the item with the largest value pops first.
+/// This makes the pop order deterministic, regardless of insertion order.
 #[derive(PartialEq, Eq, Clone)]
 pub struct PriorityQueueItem<K, V> {
+    /// Any `Ord` type works, like integer centimeters or a `Duration`
     pub cost: K,
     pub value: V,
 }
@@ -26,7 +32,90 @@ impl<K: Ord, V: Ord> Ord for PriorityQueueItem<K, V> {
         if ord != Ordering::Equal {
             return ord;
         }
-        // The tie-breaker is arbitrary, based on the value
+        // The tie-breaker is arbitrary, but deterministic, based on the value
         self.value.cmp(&other.value)
     }
 }
+
+/// Find the cheapest path from `start` to any node satisfying `is_goal`. `neighbors` returns the
+/// nodes reachable from a node, with the cost of each step. Costs must be non-negative, and
+/// `C::default()` is treated as zero. Returns the path, including `start` and the goal, and its
+/// total cost, or `None` if no goal is reachable.
+pub fn dijkstra<N, C, NF, GF>(start: N, neighbors: NF, is_goal: GF) -> Option<(Vec<N>, C)>
+where
+    N: Ord + Copy,
+    C: Ord + Copy + Add<Output = C> + Default,
+    NF: Fn(N) -> Vec<(N, C)>,
+    GF: Fn(N) -> bool,
+{
+    let mut best_cost: BTreeMap<N, C> = BTreeMap::new();
+    let mut backrefs: BTreeMap<N, N> = BTreeMap::new();
+    let mut queue = BinaryHeap::new();
+    best_cost.insert(start, C::default());
+    queue.push(PriorityQueueItem::new(C::default(), start));
+
+    while let Some(current) = queue.pop() {
+        if best_cost
+            .get(&current.value)
+            .is_some_and(|cost| current.cost > *cost)
+        {
+            continue;
+        }
+        if is_goal(current.value) {
+            let mut path = vec![current.value];
+            while let Some(prev) = backrefs.get(path.last().unwrap()) {
+                path.push(*prev);
+            }
+            path.reverse();
+            return Some((path, current.cost));
+        }
+        for (next, step) in neighbors(current.value) {
+            let cost = current.cost + step;
+            if best_cost.get(&next).is_some_and(|prev| cost >= *prev) {
+                continue;
+            }
+            best_cost.insert(next, cost);
+            backrefs.insert(next, current.value);
+            queue.push(PriorityQueueItem::new(cost, next));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BinaryHeap;
+
+    use super::*;
+
+    #[test]
+    fn deterministic_ties() {
+        let mut queue = BinaryHeap::new();
+        for value in [2, 5, 1, 4] {
+            queue.push(PriorityQueueItem::new(10, value));
+        }
+        queue.push(PriorityQueueItem::new(3, 0));
+
+        let order: Vec<usize> = std::iter::from_fn(|| queue.pop().map(|x| x.value)).collect();
+        assert_eq!(order, vec![0, 5, 4, 2, 1]);
+    }
+
+    #[test]
+    fn shortest_path() {
+        let graph = BTreeMap::from([
+            ('a', vec![('b', 1), ('c', 4)]),
+            ('b', vec![('c', 1), ('d', 5)]),
+            ('c', vec![('d', 1)]),
+            ('d', vec![]),
+            ('e', vec![('a', 1)]),
+        ]);
+        let neighbors = |n: char| graph[&n].clone();
+
+        assert_eq!(
+            dijkstra('a', neighbors, |n| n == 'd'),
+            Some((vec!['a', 'b', 'c', 'd'], 3))
+        );
+        assert_eq!(dijkstra('a', neighbors, |n| n == 'a'), Some((vec!['a'], 0)));
+        assert_eq!(dijkstra('a', neighbors, |n| n == 'e'), None);
+    }
+}
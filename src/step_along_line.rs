@@ -0,0 +1,204 @@
+use geo::{EuclideanLength, Line, LineInterpolatePoint, LineString, Point};
+
+/// Walk along a linestring, returning points every `interval` meters, starting with the first
+/// point. The last point isn't included, unless it happens to land on an interval.
+pub fn step_along_line(linestring: &LineString, interval: f64) -> Vec<Point> {
+    step_along_line_from(linestring, interval, 0.0, None)
+}
+
+/// Like `step_along_line`, but the first point is `start_offset_meters` from the start of the
+/// line, and at most `max_points` are returned. Sampling stops early, so this is cheap to use on
+/// long lines when only a few points are needed.
+pub fn step_along_line_from(
+    linestring: &LineString,
+    interval: f64,
+    start_offset_meters: f64,
+    max_points: Option<usize>,
+) -> Vec<Point> {
+    walk(
+        linestring,
+        interval,
+        start_offset_meters,
+        max_points.unwrap_or(usize::MAX),
+    )
+    .into_iter()
+    .map(|(pt, _)| pt)
+    .collect()
+}
+
+/// Return exactly `n` evenly spaced points along a linestring, starting and ending exactly at
+/// its endpoints. If `n` is 1, only the start is returned. A zero-length line repeats its start.
+pub fn sample_evenly(linestring: &LineString, n: usize) -> Vec<Point> {
+    let (Some(first), Some(last)) = (linestring.0.first(), linestring.0.last()) else {
+        return Vec::new();
+    };
+    if n == 1 || linestring.euclidean_length() == 0.0 {
+        return vec![Point(*first); n];
+    }
+    let mut result = Vec::with_capacity(n);
+    result.push(Point(*first));
+    for i in 1..n.saturating_sub(1) {
+        result.extend(linestring.line_interpolate_point(i as f64 / (n - 1) as f64));
+    }
+    if n >= 2 {
+        result.push(Point(*last));
+    }
+    result
+}
+
+/// Find the point some distance along a linestring. Returns `None` if the distance is negative
+/// or longer than the line.
+pub fn point_at_distance(linestring: &LineString, dist_meters: f64) -> Option<Point> {
+    let length = linestring.euclidean_length();
+    if dist_meters < 0.0 || dist_meters > length {
+        return None;
+    }
+    if length == 0.0 {
+        return linestring.0.first().map(|pt| Point(*pt));
+    }
+    linestring.line_interpolate_point(dist_meters / length)
+}
+
+/// Find the point some fraction along a linestring. Returns `None` if the fraction isn't in [0,
+/// 1].
+pub fn fractional_position(linestring: &LineString, fraction: f64) -> Option<Point> {
+    if !(0.0..=1.0).contains(&fraction) {
+        return None;
+    }
+    linestring.line_interpolate_point(fraction)
+}
+
+/// Like `step_along_line`, but also returns the heading of the line at each point, in degrees.
+/// The heading is `atan2(dy, dx)` of the segment containing the point, so 0 points along +x and 90
+/// along +y. In Mercator world-space, y grows downwards, so there 0 is east, 90 is south, and the
+/// angle increases clockwise as drawn. Zero-length lines produce nothing.
+pub fn step_along_line_with_headings(linestring: &LineString, interval: f64) -> Vec<(Point, f64)> {
+    walk(linestring, interval, 0.0, usize::MAX)
+        .into_iter()
+        .map(|(pt, line)| (pt, line.dy().atan2(line.dx()).to_degrees()))
+        .collect()
+}
+
+/// Walk along a linestring, returning points every `interval` meters, each paired with the
+/// non-empty segment containing it
+fn walk(
+    linestring: &LineString,
+    interval: f64,
+    start_offset_meters: f64,
+    max_points: usize,
+) -> Vec<(Point, Line)> {
+    let mut result = Vec::new();
+    let length = linestring.euclidean_length();
+    if length == 0.0 || interval <= 0.0 || start_offset_meters < 0.0 {
+        return result;
+    }
+
+    let mut dist_along = start_offset_meters;
+    // How far along the line the current segment starts
+    let mut segment_start = 0.0;
+    let mut lines = linestring.lines().filter(|l| l.euclidean_length() > 0.0);
+    let Some(mut line) = lines.next() else {
+        return result;
+    };
+    while dist_along <= length && result.len() < max_points {
+        // Find the segment containing this point. Stay on the last segment to handle floating
+        // point error at the end.
+        while dist_along > segment_start + line.euclidean_length() {
+            let Some(next) = lines.next() else {
+                break;
+            };
+            segment_start += line.euclidean_length();
+            line = next;
+        }
+
+        let fraction = ((dist_along - segment_start) / line.euclidean_length()).clamp(0.0, 1.0);
+        result.push((Point(line.start + (line.end - line.start) * fraction), line));
+        dist_along += interval;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::line_string;
+
+    use super::*;
+
+    #[test]
+    fn headings() {
+        let linestring = line_string![(x: 0.0, y: 0.0), (x: 10.0, y: 0.0), (x: 10.0, y: 10.0)];
+        // East, then south in world-space, where y points down
+        let steps = step_along_line_with_headings(&linestring, 5.0);
+        let expected = vec![
+            ((0.0, 0.0), 0.0),
+            ((5.0, 0.0), 0.0),
+            ((10.0, 0.0), 0.0),
+            ((10.0, 5.0), 90.0),
+            ((10.0, 10.0), 90.0),
+        ];
+        assert_eq!(steps.len(), expected.len());
+        for ((pt, heading), ((x, y), expected_heading)) in steps.into_iter().zip(expected) {
+            assert_eq!(pt, Point::new(x, y));
+            assert!((heading - expected_heading).abs() < 1e-9);
+        }
+
+        let empty = line_string![(x: 1.0, y: 1.0), (x: 1.0, y: 1.0)];
+        assert!(step_along_line_with_headings(&empty, 5.0).is_empty());
+    }
+
+    #[test]
+    fn offset_and_max_points() {
+        let linestring = line_string![(x: 0.0, y: 0.0), (x: 10.0, y: 0.0)];
+        assert_eq!(
+            step_along_line_from(&linestring, 4.0, 1.0, None),
+            vec![
+                Point::new(1.0, 0.0),
+                Point::new(5.0, 0.0),
+                Point::new(9.0, 0.0)
+            ]
+        );
+        assert_eq!(
+            step_along_line_from(&linestring, 4.0, 1.0, Some(2)),
+            vec![Point::new(1.0, 0.0), Point::new(5.0, 0.0)]
+        );
+        assert!(step_along_line_from(&linestring, 4.0, 11.0, None).is_empty());
+        assert!(step_along_line_from(&linestring, 4.0, 0.0, Some(0)).is_empty());
+    }
+
+    #[test]
+    fn evenly() {
+        let linestring = line_string![(x: 0.0, y: 0.0), (x: 10.0, y: 0.0), (x: 10.0, y: 10.0)];
+        assert_eq!(
+            sample_evenly(&linestring, 5),
+            vec![
+                Point::new(0.0, 0.0),
+                Point::new(5.0, 0.0),
+                Point::new(10.0, 0.0),
+                Point::new(10.0, 5.0),
+                Point::new(10.0, 10.0)
+            ]
+        );
+        assert_eq!(
+            sample_evenly(&linestring, 2),
+            vec![Point::new(0.0, 0.0), Point::new(10.0, 10.0)]
+        );
+        assert_eq!(sample_evenly(&linestring, 1), vec![Point::new(0.0, 0.0)]);
+        assert!(sample_evenly(&linestring, 0).is_empty());
+    }
+
+    #[test]
+    fn single_positions() {
+        let linestring = line_string![(x: 0.0, y: 0.0), (x: 10.0, y: 0.0), (x: 10.0, y: 10.0)];
+        assert_eq!(
+            point_at_distance(&linestring, 15.0),
+            Some(Point::new(10.0, 5.0))
+        );
+        assert_eq!(point_at_distance(&linestring, 20.1), None);
+        assert_eq!(point_at_distance(&linestring, -1.0), None);
+        assert_eq!(
+            fractional_position(&linestring, 0.25),
+            Some(Point::new(5.0, 0.0))
+        );
+        assert_eq!(fractional_position(&linestring, 1.5), None);
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -19,11 +20,58 @@ impl Tags {
         self.0.get(k)
     }
 
+    /// Parse a value, returning `None` if it's missing or invalid
+    pub fn get_parsed<T: FromStr>(&self, k: &str) -> Option<T> {
+        self.0.get(k)?.parse().ok()
+    }
+
+    /// Parse a number with an optional `km/h`, `mph`, or `m` suffix, returning the unit
+    /// separately. No unit conversion happens, so callers must check the unit; a bare number
+    /// returns `None` for it, meaning OSM's default unit for the key.
+    pub fn get_f64(&self, k: &str) -> Option<(f64, Option<&'static str>)> {
+        let value = self.0.get(k)?.trim();
+        for unit in ["km/h", "mph", "m"] {
+            if let Some(x) = value.strip_suffix(unit) {
+                return Some((x.trim_end().parse().ok()?, Some(unit)));
+            }
+        }
+        Some((value.parse().ok()?, None))
+    }
+
+    /// Look up a key, ignoring ASCII case. An exact match is preferred.
+    pub fn get_ci(&self, k: &str) -> Option<&String> {
+        self.0.get(k).or_else(|| {
+            self.0
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(k))
+                .map(|(_, v)| v)
+        })
+    }
+
+    /// True if the value is `yes`, `true`, or `1`, ignoring ASCII case
+    pub fn is_truthy(&self, k: &str) -> bool {
+        self.0.get(k).is_some_and(|v| {
+            ["yes", "true", "1"]
+                .into_iter()
+                .any(|x| v.eq_ignore_ascii_case(x))
+        })
+    }
+
+    /// True if the value is `no`, `false`, or `0`, ignoring ASCII case. A missing or other value
+    /// is neither truthy nor falsy.
+    pub fn is_falsy(&self, k: &str) -> bool {
+        self.0.get(k).is_some_and(|v| {
+            ["no", "false", "0"]
+                .into_iter()
+                .any(|x| v.eq_ignore_ascii_case(x))
+        })
+    }
+
     pub fn has(&self, k: &str) -> bool {
         self.0.contains_key(k)
     }
 
-    pub fn has_any(&self, keys: Vec<&str>) -> bool {
+    pub fn has_any<'a>(&self, keys: impl IntoIterator<Item = &'a str>) -> bool {
         keys.into_iter().any(|k| self.0.contains_key(k))
     }
 
@@ -31,16 +79,161 @@ impl Tags {
         self.0.get(k) == Some(&v.to_string())
     }
 
-    pub fn is_any(&self, k: &str, values: Vec<&str>) -> bool {
+    pub fn is_any<'a>(&self, k: &str, values: impl IntoIterator<Item = &'a str>) -> bool {
         if let Some(v) = self.0.get(k) {
-            values.contains(&v.as_ref())
+            values.into_iter().any(|x| x == v.as_str())
         } else {
             false
         }
     }
 
-    pub fn is_any_key(&self, keys: Vec<&'static str>, value: &str) -> bool {
-        keys.iter().any(|k| self.is(k, value))
+    pub fn is_any_key<'a>(&self, keys: impl IntoIterator<Item = &'a str>, value: &str) -> bool {
+        keys.into_iter().any(|k| self.is(k, value))
+    }
+
+    /// Split a value like `lane;track` into its parts, trimming whitespace and skipping empty
+    /// parts. Returns nothing if the key is missing.
+    pub fn get_multi(&self, k: &str) -> Vec<&str> {
+        match self.0.get(k) {
+            Some(v) => v
+                .split(';')
+                .map(|x| x.trim())
+                .filter(|x| !x.is_empty())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// True if the value, treated as a semicolon-separated list, contains `v`
+    pub fn has_value(&self, k: &str, v: &str) -> bool {
+        self.get_multi(k).contains(&v)
+    }
+
+    /// The `name` tag with abbreviations like `St` and `Rd` spelled out, for display. Uses
+    /// `ENGLISH_ABBREVIATIONS`.
+    pub fn expanded_name(&self) -> Option<String> {
+        self.expanded_name_with(ENGLISH_ABBREVIATIONS)
+    }
+
+    /// Like `expanded_name`, but with a different table of `(abbreviation, expansion)` pairs.
+    /// Abbreviations match whole words, case-sensitively, with or without a trailing period. The
+    /// first word is never expanded, so `St Mary's Rd` becomes `St Mary's Road`, not `Street
+    /// Mary's Road`.
+    pub fn expanded_name_with(&self, abbreviations: &[(&str, &str)]) -> Option<String> {
+        let name = self.0.get("name")?;
+        let words: Vec<&str> = name
+            .split(' ')
+            .enumerate()
+            .map(|(idx, word)| {
+                if idx == 0 {
+                    return word;
+                }
+                let bare = word.strip_suffix('.').unwrap_or(word);
+                abbreviations
+                    .iter()
+                    .find(|(abbrev, _)| *abbrev == bare)
+                    .map(|(_, expanded)| *expanded)
+                    .unwrap_or(word)
+            })
+            .collect();
+        Some(words.join(" "))
+    }
+
+    /// Count the lanes in each direction, applying these defaults:
+    ///
+    /// - `lanes:both_ways` (like a shared center turn lane) defaults to 0
+    /// - For oneway roads, as interpreted by `oneway`, all lanes go the one way.
+    ///   `lanes:forward` (or `lanes:backward` for `oneway=-1`) or `lanes` is used, defaulting to 1.
+    /// - For two-way roads, explicit `lanes:forward` and `lanes:backward` are used. If only one
+    ///   is given with `lanes`, the other gets the remainder, after `lanes:both_ways`.
+    /// - If only `lanes` is given, the remainder after `lanes:both_ways` is split, with forward
+    ///   getting `ceil(remainder / 2)`.
+    /// - Anything else missing defaults to 1 lane per direction.
+    pub fn lane_counts(&self) -> LaneCounts {
+        let total = self.get_parsed::<usize>("lanes");
+        let forward = self.get_parsed::<usize>("lanes:forward");
+        let backward = self.get_parsed::<usize>("lanes:backward");
+        let both_ways = self.get_parsed::<usize>("lanes:both_ways").unwrap_or(0);
+
+        match self.oneway() {
+            Oneway::Forward => {
+                return LaneCounts {
+                    forward: forward.or(total).unwrap_or(1),
+                    backward: 0,
+                    both_ways,
+                };
+            }
+            Oneway::Backward => {
+                return LaneCounts {
+                    forward: 0,
+                    backward: backward.or(total).unwrap_or(1),
+                    both_ways,
+                };
+            }
+            Oneway::No => {}
+        }
+
+        let (forward, backward) = match (forward, backward, total) {
+            (Some(f), Some(b), _) => (f, b),
+            (Some(f), None, Some(t)) => (f, t.saturating_sub(both_ways + f)),
+            (None, Some(b), Some(t)) => (t.saturating_sub(both_ways + b), b),
+            (None, None, Some(t)) => {
+                let remainder = t.saturating_sub(both_ways);
+                let f = remainder.div_ceil(2);
+                (f, remainder - f)
+            }
+            (f, b, None) => (f.unwrap_or(1), b.unwrap_or(1)),
+        };
+        LaneCounts {
+            forward,
+            backward,
+            both_ways,
+        }
+    }
+
+    /// Interpret `oneway`, `junction=roundabout`, and `highway=motorway`, relative to the way's
+    /// node order. Roundabouts and motorways are oneway unless tagged `oneway=no`. Reversible
+    /// ways change direction over time, so they're treated as two-way.
+    pub(crate) fn oneway(&self) -> Oneway {
+        if self.is_any("oneway", ["yes", "true", "1"]) {
+            return Oneway::Forward;
+        }
+        if self.is("oneway", "-1") {
+            return Oneway::Backward;
+        }
+        if (self.is("junction", "roundabout") || self.is("highway", "motorway"))
+            && !self.is("oneway", "no")
+        {
+            return Oneway::Forward;
+        }
+        Oneway::No
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.0.iter()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.0.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Copy all tags whose key starts with `prefix`, like `cycleway:`
+    pub fn filter_keys(&self, prefix: &str) -> Tags {
+        Self(
+            self.0
+                .iter()
+                .filter(|(k, _)| k.starts_with(prefix))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        )
     }
 
     pub fn insert<K: Into<String>, V: Into<String>>(&mut self, k: K, v: V) {
@@ -50,6 +243,94 @@ impl Tags {
     pub fn remove(&mut self, k: &str) -> Option<String> {
         self.0.remove(k)
     }
+
+    /// Overlay other tags on top of these. Values from `other` win.
+    pub fn merge(&mut self, other: &Tags) {
+        for (k, v) in &other.0 {
+            self.0.insert(k.clone(), v.clone());
+        }
+    }
+
+    /// Calculate what changed going from these tags to `other`
+    pub fn diff(&self, other: &Tags) -> TagDiff {
+        let mut diff = TagDiff::default();
+        for (k, old) in &self.0 {
+            match other.0.get(k) {
+                Some(new) if new != old => {
+                    diff.changed.insert(k.clone(), (old.clone(), new.clone()));
+                }
+                Some(_) => {}
+                None => {
+                    diff.removed.insert(k.clone(), old.clone());
+                }
+            }
+        }
+        for (k, new) in &other.0 {
+            if !self.0.contains_key(k) {
+                diff.added.insert(k.clone(), new.clone());
+            }
+        }
+        diff
+    }
+}
+
+/// Common English street name abbreviations, for `Tags::expanded_name`
+pub const ENGLISH_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("Ave", "Avenue"),
+    ("Blvd", "Boulevard"),
+    ("Cl", "Close"),
+    ("Cres", "Crescent"),
+    ("Ct", "Court"),
+    ("Dr", "Drive"),
+    ("E", "East"),
+    ("Hwy", "Highway"),
+    ("Ln", "Lane"),
+    ("N", "North"),
+    ("Pl", "Place"),
+    ("Rd", "Road"),
+    ("S", "South"),
+    ("Sq", "Square"),
+    ("St", "Street"),
+    ("Ter", "Terrace"),
+    ("W", "West"),
+];
+
+/// The number of lanes on a road in each direction, from `Tags::lane_counts`
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LaneCounts {
+    pub forward: usize,
+    pub backward: usize,
+    /// Lanes usable in either direction, like a center turn lane
+    pub both_ways: usize,
+}
+
+/// Which way traffic may travel along a way, from `Tags::oneway`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Oneway {
+    /// Only in the order of the way's nodes
+    Forward,
+    /// Only against the order of the way's nodes
+    Backward,
+    No,
+}
+
+/// The changes from one set of tags to another
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TagDiff {
+    /// Keys only in the new tags
+    pub added: BTreeMap<String, String>,
+    /// Keys only in the old tags, with their old value
+    pub removed: BTreeMap<String, String>,
+    /// Keys in both, with the (old, new) values
+    pub changed: BTreeMap<String, (String, String)>,
+}
+
+impl TagDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
 }
 
 impl From<HashMap<String, String>> for Tags {
@@ -62,6 +343,31 @@ impl From<HashMap<String, String>> for Tags {
     }
 }
 
+impl FromIterator<(String, String)> for Tags {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+/// Build from a fixed list of pairs, mostly for tests and synthetic data. Later duplicate keys
+/// win.
+///
+/// ```
+/// use utils::Tags;
+///
+/// let tags = Tags::from([("highway", "residential"), ("oneway", "yes")]);
+/// assert!(tags.is("highway", "residential"));
+/// assert_eq!(tags.len(), 2);
+/// ```
+impl<const N: usize> From<[(&str, &str); N]> for Tags {
+    fn from(pairs: [(&str, &str); N]) -> Self {
+        pairs
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,5 +379,159 @@ mod tests {
         tags.insert("key", "value");
         assert!(tags.is("key", "value"));
         assert!(tags.is_any("key", vec!["val1", "val2", "value"]));
+        assert!(tags.is_any("key", ["val1", "value"]));
+        assert!(!tags.is_any("key", ["val1", "val2"].iter().copied()));
+        assert!(tags.has_any(["other", "key"]));
+        assert!(tags.is_any_key(["other", "key"], "value"));
+    }
+
+    #[test]
+    fn parsing() {
+        let mut tags = Tags::empty();
+        tags.insert("lanes", "2");
+        tags.insert("maxspeed", "30 mph");
+        tags.insert("width", "3.5m");
+        tags.insert("maxheight", "4.2");
+        tags.insert("name", "Main Street");
+
+        assert_eq!(tags.get_parsed::<usize>("lanes"), Some(2));
+        assert_eq!(tags.get_parsed::<usize>("name"), None);
+        assert_eq!(tags.get_parsed::<usize>("missing"), None);
+        assert_eq!(tags.get_f64("maxspeed"), Some((30.0, Some("mph"))));
+        assert_eq!(tags.get_f64("width"), Some((3.5, Some("m"))));
+        assert_eq!(tags.get_f64("maxheight"), Some((4.2, None)));
+        assert_eq!(tags.get_f64("name"), None);
+    }
+
+    #[test]
+    fn booleans() {
+        let mut tags = Tags::empty();
+        tags.insert("oneway", "Yes");
+        tags.insert("lit", "0");
+        tags.insert("Name", "Main Street");
+        tags.insert("access", "private");
+
+        assert!(tags.is_truthy("oneway"));
+        assert!(!tags.is_falsy("oneway"));
+        assert!(tags.is_falsy("lit"));
+        assert!(!tags.is_truthy("access") && !tags.is_falsy("access"));
+        assert!(!tags.is_truthy("missing") && !tags.is_falsy("missing"));
+        assert_eq!(tags.get_ci("name"), Some(&"Main Street".to_string()));
+    }
+
+    #[test]
+    fn multi_values() {
+        let tags = Tags::from([("cycleway", "lane; track"), ("ref", "A1"), ("note", ";")]);
+        assert_eq!(tags.get_multi("cycleway"), vec!["lane", "track"]);
+        assert_eq!(tags.get_multi("ref"), vec!["A1"]);
+        assert!(tags.get_multi("note").is_empty());
+        assert!(tags.get_multi("missing").is_empty());
+        assert!(tags.has_value("cycleway", "track"));
+        assert!(!tags.has_value("cycleway", "lane; track"));
+        assert!(!tags.has_value("missing", "track"));
+    }
+
+    #[test]
+    fn expanded_name() {
+        let expand = |name: &str| Tags::from([("name", name)]).expanded_name().unwrap();
+        assert_eq!(expand("Main St"), "Main Street");
+        assert_eq!(expand("St Mary's Rd."), "St Mary's Road");
+        assert_eq!(expand("N Stanley Ave E"), "N Stanley Avenue East");
+        assert_eq!(expand("Main Street"), "Main Street");
+        assert_eq!(Tags::empty().expanded_name(), None);
+
+        let tags = Tags::from([("name", "Hauptstr Nord")]);
+        assert_eq!(
+            tags.expanded_name_with(&[("Nord", "Norden")]),
+            Some("Hauptstr Norden".to_string())
+        );
+        assert_eq!(tags.get("name"), Some(&"Hauptstr Nord".to_string()));
+    }
+
+    #[test]
+    fn lane_counts() {
+        let counts = |pairs: &[(&str, &str)]| {
+            let tags: Tags = pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            let c = tags.lane_counts();
+            (c.forward, c.backward, c.both_ways)
+        };
+
+        assert_eq!(counts(&[]), (1, 1, 0));
+        assert_eq!(counts(&[("lanes", "3")]), (2, 1, 0));
+        assert_eq!(
+            counts(&[("lanes", "3"), ("lanes:both_ways", "1")]),
+            (1, 1, 1)
+        );
+        assert_eq!(
+            counts(&[("lanes", "4"), ("lanes:backward", "1")]),
+            (3, 1, 0)
+        );
+        assert_eq!(
+            counts(&[("lanes:forward", "2"), ("lanes:backward", "1")]),
+            (2, 1, 0)
+        );
+        assert_eq!(counts(&[("lanes", "2"), ("oneway", "yes")]), (2, 0, 0));
+        assert_eq!(counts(&[("junction", "roundabout")]), (1, 0, 0));
+        assert_eq!(counts(&[("lanes", "2"), ("oneway", "-1")]), (0, 2, 0));
+        assert_eq!(
+            counts(&[("junction", "roundabout"), ("oneway", "-1")]),
+            (0, 1, 0)
+        );
+        assert_eq!(
+            counts(&[("highway", "motorway"), ("lanes", "3")]),
+            (3, 0, 0)
+        );
+    }
+
+    #[test]
+    fn merge_and_diff() {
+        let mut before = Tags::empty();
+        before.insert("highway", "residential");
+        before.insert("maxspeed", "20 mph");
+        before.insert("lit", "yes");
+        let mut changes = Tags::empty();
+        changes.insert("maxspeed", "30 mph");
+        changes.insert("name", "Main Street");
+
+        let mut after = before.clone();
+        after.merge(&changes);
+        after.remove("lit");
+
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff.added,
+            BTreeMap::from([("name".to_string(), "Main Street".to_string())])
+        );
+        assert_eq!(
+            diff.removed,
+            BTreeMap::from([("lit".to_string(), "yes".to_string())])
+        );
+        assert_eq!(
+            diff.changed,
+            BTreeMap::from([(
+                "maxspeed".to_string(),
+                ("20 mph".to_string(), "30 mph".to_string())
+            )])
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn filter_keys() {
+        let mut tags = Tags::empty();
+        tags.insert("highway", "primary");
+        tags.insert("cycleway:left", "lane");
+        tags.insert("cycleway:right", "no");
+
+        let cycleway = tags.filter_keys("cycleway:");
+        assert_eq!(cycleway.len(), 2);
+        assert_eq!(
+            cycleway.keys().collect::<Vec<_>>(),
+            vec!["cycleway:left", "cycleway:right"]
+        );
+        assert!(tags.filter_keys("sidewalk").is_empty());
     }
 }